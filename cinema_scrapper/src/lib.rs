@@ -92,6 +92,7 @@ mod tests {
 
     use super::*;
     #[test]
+    #[ignore = "not written yet, it only has a todo!()"]
     fn test_scrape_from_html() {
         todo!()
     }
@@ -0,0 +1,37 @@
+use crate::Error;
+
+// CLIPBOARD
+// The system clipboard when the `clipboard` feature is on, else a clipboard that only
+// lives as long as the game, which is what the browser build uses.
+#[cfg(feature = "clipboard")]
+pub fn set_text(text: String) -> Result<(), Error> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|error| Error::Clipboard(error.to_string()))
+}
+
+#[cfg(feature = "clipboard")]
+pub fn get_text() -> Result<String, Error> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|error| Error::Clipboard(error.to_string()))
+}
+
+#[cfg(not(feature = "clipboard"))]
+thread_local! {
+    static CLIPBOARD: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn set_text(text: String) -> Result<(), Error> {
+    CLIPBOARD.with(|clipboard| *clipboard.borrow_mut() = Some(text));
+    Ok(())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn get_text() -> Result<String, Error> {
+    CLIPBOARD
+        .with(|clipboard| clipboard.borrow().clone())
+        .ok_or_else(|| Error::Clipboard(String::from("nothing was copied")))
+}
+// CLIPBOARD END
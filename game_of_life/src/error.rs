@@ -0,0 +1,76 @@
+use std::fmt;
+use std::process::ExitCode;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a save file failed.
+    Io(std::io::Error),
+    /// A save file exists but its content is not a valid grid.
+    Format(String),
+    /// An RLE pattern, from the clipboard, is not a valid grid.
+    Pattern(String),
+    /// A save file was made for another grid size.
+    Dimension {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    /// The command line options can't be used together.
+    Config(String),
+    /// The clipboard can't be read or written.
+    Clipboard(String),
+}
+
+impl Error {
+    /// Short text that fits in the status bar of a small grid.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "SAVE ERROR",
+            Error::Format(_) => "BAD FILE",
+            Error::Pattern(_) => "BAD PATTERN",
+            Error::Dimension { .. } => "BAD SIZE",
+            Error::Config(_) => "BAD CONFIG",
+            Error::Clipboard(_) => "CLIPBOARD ERROR",
+        }
+    }
+
+    // Codes from sysexits.h
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::Io(_) | Error::Clipboard(_) => ExitCode::from(74),
+            Error::Format(_) | Error::Pattern(_) | Error::Dimension { .. } => ExitCode::from(65),
+            Error::Config(_) => ExitCode::from(78),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "input/output error: {error}"),
+            Error::Format(reason) => write!(f, "invalid save file: {reason}"),
+            Error::Pattern(reason) => write!(f, "invalid pattern: {reason}"),
+            Error::Dimension { expected, found } => write!(
+                f,
+                "save file is {}x{} but the grid is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            Error::Config(reason) => write!(f, "invalid configuration: {reason}"),
+            Error::Clipboard(reason) => write!(f, "clipboard error: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
@@ -1,9 +1,27 @@
-use clap::Parser;
-use std::fs::File;
-use std::io::Write;
+use clap::{Parser, ValueEnum};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use web_time::{Duration, Instant};
 use window_rs::WindowBuffer;
 use graphic::Graphic;
+use pixel_sims::Simulation;
+
+mod clipboard;
+mod error;
+pub mod pattern;
+#[cfg(test)]
+mod reference;
+pub mod save;
+pub mod selection;
+pub mod status;
+#[cfg(feature = "web")]
+pub mod web;
+
+pub use error::Error;
+pub use pixel_sims::colour::rgb;
+use pattern::Pattern;
+use selection::Selection;
+use status::{Status, STATUS_COLOUR};
 
 //CLI
 #[derive(Parser, Debug)]
@@ -16,23 +34,56 @@ pub struct Cli {
     pub height: usize,
     #[arg(long)]
     pub file_path: Option<String>,
+    /// Folder of the save slots and of the autosaves
+    #[arg(long, default_value = ".")]
+    pub save_dir: String,
+    /// Save in a new timestamped file every N generations
+    #[arg(long)]
+    pub autosave: Option<u64>,
+    /// Save when the window is closed
+    #[arg(long)]
+    pub save_on_exit: bool,
+    /// What lies beyond the edges of the grid
+    #[arg(long, value_enum, default_value_t = Topology::Bounded)]
+    pub topology: Topology,
 }
-//CLI END
 
-//COLOURS MANAGEMENT
-pub fn rgb(red: u8, green: u8, blue: u8) -> u32 {
-    let a = u32::from(red);
-    let b: u32 = u32::from(green);
-    let c = u32::from(blue);
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    /// Cells outside of the grid are always dead
+    Bounded,
+    /// The left edge touches the right one and the top edge touches the bottom one
+    Torus,
+}
+//CLI END
 
-    let new_red = a << 16;
-    let new_green = b << 8;
+//KEYS
+// Number keys choose the save slot used by Save and Load
+const SLOT_KEYS: [graphic::Key; 9] = [
+    graphic::Key::Key1,
+    graphic::Key::Key2,
+    graphic::Key::Key3,
+    graphic::Key::Key4,
+    graphic::Key::Key5,
+    graphic::Key::Key6,
+    graphic::Key::Key7,
+    graphic::Key::Key8,
+    graphic::Key::Key9,
+];
+const LOAD_KEY: graphic::Key = graphic::Key::L;
+// The selection is made with the right mouse button
+const COPY_KEY: graphic::Key = graphic::Key::C;
+const CUT_KEY: graphic::Key = graphic::Key::X;
+const PASTE_KEY: graphic::Key = graphic::Key::V;
+const ROTATE_KEY: graphic::Key = graphic::Key::R;
+const FLIP_HORIZONTAL_KEY: graphic::Key = graphic::Key::F;
+const FLIP_VERTICAL_KEY: graphic::Key = graphic::Key::U;
+const CLEAR_KEY: graphic::Key = graphic::Key::Delete;
+const RANDOM_FILL_KEY: graphic::Key = graphic::Key::N;
+//KEYS END
 
-    let final_number = new_red | new_green | c;
+pub const SELECTION_COLOUR: u32 = 0x00202060;
 
-    return final_number;
-}
-//COLOURS MANAGEMENT END
 
 // GRID CREATION
 #[derive(Debug)]
@@ -42,6 +93,13 @@ pub struct World {
     pub small_break_timer: Instant,
     pub speed: u64,
     pub colour_cell: u32,
+    pub topology: Topology,
+    pub status: Option<Status>,
+    pub generation: u64,
+    pub slot: Option<u8>,
+    pub selection: Option<Selection>,
+    selecting: bool,
+    last_autosave: u64,
 }
 
 impl World {
@@ -58,9 +116,47 @@ impl World {
             small_break_timer,
             speed,
             colour_cell,
+            topology: Topology::Bounded,
+            status: None,
+            generation: 0,
+            slot: None,
+            selection: None,
+            selecting: false,
+            last_autosave: 0,
         }
     }
 
+    /// Builds the world asked for on the command line, loading the save file if there is one.
+    pub fn from_cli(cli: &Cli) -> Result<Self, Error> {
+        if cli.width == 0 || cli.height == 0 {
+            return Err(Error::Config(format!(
+                "the grid can't be {}x{}",
+                cli.width, cli.height
+            )));
+        }
+
+        if cli.autosave == Some(0) {
+            return Err(Error::Config(String::from(
+                "autosave needs at least one generation between saves",
+            )));
+        }
+
+        let mut world = World::new(
+            WindowBuffer::new(cli.width, cli.height),
+            0,
+            Instant::now(),
+            2,
+            0x0066CC33,
+        );
+        world.topology = cli.topology;
+
+        if let Some(file_path) = &cli.file_path {
+            world.load(Path::new(file_path))?;
+        }
+
+        Ok(world)
+    }
+
     pub fn space_count(&mut self) -> usize {
         self.space_count
     }
@@ -72,7 +168,19 @@ impl World {
     }
     pub fn update(&mut self) {
         if self.space_count % 2 == 0 {
-            self.check_surroundings()
+            self.check_surroundings();
+            self.generation += 1;
+        }
+    }
+
+    /// The cell at (x, y), coordinates outside of the grid wrap around on a torus.
+    pub fn cell(&self, x: isize, y: isize) -> Option<u32> {
+        match self.topology {
+            Topology::Bounded => self.window_buffer.get(x, y),
+            Topology::Torus => self.window_buffer.get(
+                x.rem_euclid(self.window_buffer.width() as isize),
+                y.rem_euclid(self.window_buffer.height() as isize),
+            ),
         }
     }
 
@@ -86,28 +194,28 @@ impl World {
                 let x = x as isize;
                 let y = y as isize;
 
-                if self.window_buffer.get(x - 1, y - 1) == Some(self.colour_cell) {
+                if self.cell(x - 1, y - 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x - 1, y) == Some(self.colour_cell) {
+                if self.cell(x - 1, y) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x - 1, y + 1) == Some(self.colour_cell) {
+                if self.cell(x - 1, y + 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x, y - 1) == Some(self.colour_cell) {
+                if self.cell(x, y - 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x, y + 1) == Some(self.colour_cell) {
+                if self.cell(x, y + 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x + 1, y - 1) == Some(self.colour_cell) {
+                if self.cell(x + 1, y - 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if (self.window_buffer.get(x + 1, y)) == Some(self.colour_cell) {
+                if (self.cell(x + 1, y)) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
-                if self.window_buffer.get(x + 1, y + 1) == Some(self.colour_cell) {
+                if self.cell(x + 1, y + 1) == Some(self.colour_cell) {
                     colored_cells_counter += 1;
                 }
 
@@ -128,32 +236,188 @@ impl World {
         self.window_buffer = next_iteration;
     }
 
-    pub fn handle_user_input <W: Graphic>(&mut self, window: &W, cli: &Cli) -> std::io::Result<()> {
-        if let Some((x, y)) = window.get_mouse_pos(graphic::Mouse::Discard) {
+    /// Replaces the grid and the speed with the ones stored in a save file made for a grid
+    /// of the same size.
+    pub fn load_save_file<R: Read>(&mut self, save_file: R) -> Result<(), Error> {
+        let expected = (self.window_buffer.width(), self.window_buffer.height());
+        let save = save::read(save_file, expected)?;
+        self.window_buffer = save.window_buffer;
+        self.speed = save.speed;
+
+        Ok(())
+    }
+
+    /// Loads a save file made for a grid of the same size, the window can't be resized.
+    pub fn load(&mut self, path: &Path) -> Result<(), Error> {
+        let expected = (self.window_buffer.width(), self.window_buffer.height());
+        let save = save::load_from_path(path, expected)?;
+
+        self.window_buffer = save.window_buffer;
+        self.speed = save.speed;
+
+        Ok(())
+    }
+
+    /// The file used by Save and Load: the chosen slot, else the file of the command line.
+    pub fn save_path(&self, cli: &Cli) -> PathBuf {
+        match (self.slot, &cli.file_path) {
+            (Some(slot), _) => save::slot_path(Path::new(&cli.save_dir), slot),
+            (None, Some(file_path)) => PathBuf::from(file_path),
+            (None, None) => PathBuf::from("save_file"),
+        }
+    }
+
+    /// Saves in a new file when `--autosave` generations went by since the last autosave.
+    pub fn autosave(&mut self, cli: &Cli) -> Result<Option<PathBuf>, Error> {
+        let Some(every) = cli.autosave else {
+            return Ok(None);
+        };
+        if self.generation < self.last_autosave + every {
+            return Ok(None);
+        }
+
+        self.last_autosave = self.generation;
+        let path = save::autosave_path(Path::new(&cli.save_dir), self.generation);
+        save::save_to_path(self, &path)?;
+
+        Ok(Some(path))
+    }
+
+    /// Replaces the selection by its transformed cells, anchored on its top left corner.
+    pub fn transform_selection(&mut self, selection: Selection, transform: fn(&Pattern) -> Pattern) {
+        let pattern = transform(&selection.copy(&self.window_buffer, self.colour_cell));
+        selection.clear(&mut self.window_buffer);
+
+        let position = (selection.left(), selection.top());
+        let transformed = selection::paste(&pattern, &mut self.window_buffer, position, self.colour_cell);
+        self.selection = Some(transformed);
+    }
+
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some(Status::new(message));
+    }
+
+    /// Errors don't stop the game, the player is told about them.
+    pub fn report(&mut self, error: Error) {
+        eprintln!("{error}");
+        self.set_status(error.status());
+    }
+
+    /// The grid as it should be displayed, with the status message on top of it.
+    pub fn frame(&self) -> WindowBuffer {
+        let mut frame = WindowBuffer::new(self.window_buffer.width(), self.window_buffer.height());
+        for y in 0..frame.height() {
+            for x in 0..frame.width() {
+                frame[(x, y)] = self.window_buffer[(x, y)];
+            }
+        }
+
+        // Dead cells of the selection are tinted, alive ones keep their colour
+        if let Some(selection) = &self.selection {
+            for y in 0..frame.height() {
+                for x in 0..frame.width() {
+                    if selection.contains(x, y) && frame[(x, y)] == 0 {
+                        frame[(x, y)] = SELECTION_COLOUR;
+                    }
+                }
+            }
+        }
+
+        if let Some(status) = &self.status {
+            if status.is_visible() {
+                status::draw_text(&mut frame, &status.message, STATUS_COLOUR);
+            }
+        }
+
+        frame
+    }
+
+    /// A failed action is reported, the other keys of the frame are still handled.
+    pub fn handle_user_input <W: Graphic>(&mut self, window: &W, cli: &Cli) {
+        let mouse_pos = window.get_mouse_pos(graphic::Mouse::Discard);
+
+        if let Some((x, y)) = mouse_pos {
             if window.get_mouse_down(graphic::Mouse::Left) {
                 self.window_buffer[(x as usize, y as usize)] = self.colour_cell;
             }
+
+            if window.get_mouse_down(graphic::Mouse::Right) {
+                let position = (x as usize, y as usize);
+                match &mut self.selection {
+                    Some(selection) if self.selecting => selection.end = position,
+                    _ => self.selection = Some(Selection::new(position)),
+                }
+            }
+        }
+        self.selecting = window.get_mouse_down(graphic::Mouse::Right);
+
+        if let Some(selection) = self.selection {
+            if window.is_key_pressed(COPY_KEY) || window.is_key_pressed(CUT_KEY) {
+                let pattern = selection.copy(&self.window_buffer, self.colour_cell);
+                match clipboard::set_text(pattern.to_rle()) {
+                    Ok(()) => self.set_status("COPIED"),
+                    Err(error) => self.report(error),
+                }
+            }
+            if window.is_key_pressed(CUT_KEY) {
+                selection.clear(&mut self.window_buffer);
+            }
+            if window.is_key_pressed(ROTATE_KEY) {
+                self.transform_selection(selection, Pattern::rotate);
+            }
+            if window.is_key_pressed(FLIP_HORIZONTAL_KEY) {
+                self.transform_selection(selection, Pattern::flip_horizontal);
+            }
+            if window.is_key_pressed(FLIP_VERTICAL_KEY) {
+                self.transform_selection(selection, Pattern::flip_vertical);
+            }
+            if window.is_key_pressed(CLEAR_KEY) {
+                selection.clear(&mut self.window_buffer);
+            }
+            if window.is_key_pressed(RANDOM_FILL_KEY) {
+                selection.fill_random(&mut self.window_buffer, self.colour_cell, &mut rand::thread_rng());
+            }
+        }
+
+        if window.is_key_pressed(PASTE_KEY) {
+            let grid = (self.window_buffer.width(), self.window_buffer.height());
+            match clipboard::get_text().and_then(|text| Pattern::from_rle(&text, grid)) {
+                Ok(pattern) => {
+                    let position = match (mouse_pos, self.selection) {
+                        (Some((x, y)), _) => (x as usize, y as usize),
+                        (None, Some(selection)) => (selection.left(), selection.top()),
+                        (None, None) => (0, 0),
+                    };
+                    let pasted = selection::paste(&pattern, &mut self.window_buffer, position, self.colour_cell);
+                    self.selection = Some(pasted);
+                }
+                Err(error) => self.report(error),
+            }
         }
 
         if window.is_key_pressed(graphic::Key::Quit,) {
             self.window_buffer.reset();
         }
 
-        if window.is_key_pressed(graphic::Key::Save) {
-            let mut save_file = File::create("save_file")?;
-
-            if cli.file_path != None {
-                save_file = File::create(cli.file_path.clone().unwrap())?;
+        for (slot, key) in SLOT_KEYS.iter().enumerate() {
+            if window.is_key_pressed(*key) {
+                self.slot = Some(slot as u8 + 1);
+                self.set_status(format!("SLOT {}", slot + 1));
             }
-            save_file.write_all(&self.window_buffer.width().to_be_bytes())?;
-            save_file.write_all(&self.window_buffer.height().to_be_bytes())?;
-            save_file.write_all(&self.speed().to_be_bytes())?;
+        }
 
-            for number in &self.window_buffer.buffer() {
-                save_file.write_all(&number.to_be_bytes())?;
+        if window.is_key_pressed(graphic::Key::Save) {
+            match save::save_to_path(self, &self.save_path(cli)) {
+                Ok(()) => self.set_status("SAVED"),
+                Err(error) => self.report(error),
             }
+        }
 
-            save_file.flush()?;
+        if window.is_key_pressed(LOAD_KEY) {
+            match self.load(&self.save_path(cli)) {
+                Ok(()) => self.set_status("LOADED"),
+                Err(error) => self.report(error),
+            }
         }
 
         if window.is_key_pressed(graphic::Key::Up) {
@@ -174,11 +438,47 @@ impl World {
             });
             self.small_break_timer = Instant::now();
         }
+    }
+}
 
-        Ok(())
+
+// MAIN LOOP
+/// A world with the options it was started with, what the main loop drives.
+pub struct Game {
+    pub world: World,
+    pub cli: Cli,
+}
+
+impl Game {
+    /// Errors during the game don't stop it, the player is told about them.
+    fn report(&mut self, result: Result<(), Error>) {
+        if let Err(error) = result {
+            self.world.report(error);
+        }
     }
 }
 
+impl<W: Graphic> Simulation<W> for Game {
+    fn update(&mut self) {
+        self.world.update();
+        let autosave = self.world.autosave(&self.cli).map(|_| ());
+        self.report(autosave);
+    }
+
+    fn render(&self, buffer: &mut WindowBuffer) {
+        *buffer = self.world.frame();
+    }
+
+    fn handle_input(&mut self, window: &W) {
+        self.world.handle_user_input(window, &self.cli);
+    }
+
+    // `speed` is the number of seconds between two generations
+    fn tick(&self) -> Duration {
+        Duration::from_secs(self.world.speed)
+    }
+}
+// MAIN LOOP END
 
 //TESTS
 
@@ -195,6 +495,98 @@ mod test {
         assert_eq!(rgb(0x12, 0x34, 0x56), 0x00_12_34_56);
     }
 
+    #[test]
+    fn test_truncated_save_file() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        bytes.extend_from_slice(&7u64.to_be_bytes());
+        bytes.extend_from_slice(&0x0066CC33u32.to_be_bytes());
+
+        let mut buffer = World::new(WindowBuffer::new(2, 2), 0, Instant::now(), 3, 0x0066CC33);
+        let error = buffer.load_save_file(&bytes[..]).unwrap_err();
+
+        assert!(matches!(error, Error::Format(_)));
+        assert_eq!(buffer.speed, 3);
+    }
+
+    #[test]
+    fn test_huge_save_file_is_refused() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(1u64 << 31).to_be_bytes());
+        bytes.extend_from_slice(&(1u64 << 31).to_be_bytes());
+        bytes.extend_from_slice(&7u64.to_be_bytes());
+
+        let mut buffer = World::new(WindowBuffer::new(5, 4), 0, Instant::now(), 3, 0x0066CC33);
+        let error = buffer.load_save_file(&bytes[..]).unwrap_err();
+
+        assert!(matches!(error, Error::Dimension { .. }));
+        assert_eq!(buffer.window_buffer.width(), 5);
+    }
+
+    #[test]
+    fn test_empty_grid_is_a_config_error() {
+        let cli = Cli {
+            width: 0,
+            height: 30,
+            file_path: None,
+            save_dir: String::from("."),
+            autosave: None,
+            save_on_exit: false,
+            topology: Topology::Bounded,
+        };
+        let error = World::from_cli(&cli).unwrap_err();
+
+        assert!(matches!(error, Error::Config(_)));
+        assert_eq!(error.exit_code(), std::process::ExitCode::from(78));
+    }
+
+    #[test]
+    fn test_save_path() {
+        let mut cli = Cli::parse_from(["game_of_life"]);
+        let mut buffer = World::from_cli(&cli).unwrap();
+        assert_eq!(buffer.save_path(&cli), PathBuf::from("save_file"));
+
+        cli.file_path = Some(String::from("glider"));
+        assert_eq!(buffer.save_path(&cli), PathBuf::from("glider"));
+
+        buffer.slot = Some(2);
+        assert_eq!(buffer.save_path(&cli), PathBuf::from("./slot-2.save"));
+    }
+
+    #[test]
+    fn test_autosave() {
+        let save_dir = std::env::temp_dir().join("game_of_life_test_autosave");
+        let _ = std::fs::remove_dir_all(&save_dir);
+        std::fs::create_dir_all(&save_dir).unwrap();
+
+        let cli = Cli::parse_from([
+            "game_of_life",
+            "--width",
+            "5",
+            "--height",
+            "4",
+            "--autosave",
+            "2",
+            "--save-dir",
+            save_dir.to_str().unwrap(),
+        ]);
+        let mut buffer = World::from_cli(&cli).unwrap();
+
+        buffer.update();
+        assert!(buffer.autosave(&cli).unwrap().is_none());
+        buffer.update();
+        let path = buffer.autosave(&cli).unwrap().unwrap();
+        assert!(buffer.autosave(&cli).unwrap().is_none());
+
+        let mut loaded = World::from_cli(&cli).unwrap();
+        loaded.speed = 0;
+        loaded.load(&path).unwrap();
+        assert_eq!(loaded.speed, 2);
+
+        std::fs::remove_dir_all(&save_dir).unwrap();
+    }
+
     #[test]
     fn cells_life_square() {
         let mut buffer: World = World::new(WindowBuffer::new(5, 4), 0, Instant::now(), 0, 0x0066CC33);
@@ -290,4 +682,44 @@ mod test {
         "###
         );
     }
+
+    // Only knows which keys are pressed on this frame
+    struct Keys(Vec<graphic::Key>);
+
+    impl Graphic for Keys {
+        fn new(_name: &str, _width: usize, _height: usize) -> Self {
+            Keys(Vec::new())
+        }
+        fn is_open(&self) -> bool {
+            true
+        }
+        fn is_key_down(&self, key: graphic::Key) -> bool {
+            self.0.contains(&key)
+        }
+        fn is_key_pressed(&self, key: graphic::Key) -> bool {
+            self.0.contains(&key)
+        }
+        fn get_mouse_pos(&self, _mouse: graphic::Mouse) -> Option<(f32, f32)> {
+            None
+        }
+        fn get_mouse_down(&self, _mouse: graphic::Mouse) -> bool {
+            false
+        }
+        fn get_keys_released(&self) -> Vec<graphic::Key> {
+            Vec::new()
+        }
+        fn update_with_buffer(&mut self, _buffer: &WindowBuffer) {}
+    }
+
+    #[test]
+    fn test_failed_load_keeps_the_other_keys() {
+        let missing = std::env::temp_dir().join("game_of_life_test_missing_save");
+        let _ = std::fs::remove_file(&missing);
+        let cli = Cli::parse_from(["game_of_life", "--file-path", missing.to_str().unwrap()]);
+        let mut world = World::new(WindowBuffer::new(4, 4), 0, Instant::now(), 1, 0x0066CC33);
+
+        world.handle_user_input(&Keys(vec![LOAD_KEY, graphic::Key::Down]), &cli);
+        assert_eq!(world.speed, 2);
+        assert_eq!(world.status.unwrap().message, "SAVE ERROR");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,299 @@
+use crate::Error;
+
+// RLE lines are kept under 70 characters, like Golly does
+const RLE_LINE_LENGTH: usize = 70;
+
+/// A rectangle of cells cut off the grid, alive or dead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<bool>,
+}
+
+impl Pattern {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![false; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.cells[y * self.width + x] = alive;
+    }
+
+    /// Quarter turn clockwise, the width and the height are swapped.
+    pub fn rotate(&self) -> Pattern {
+        let mut rotated = Pattern::new(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                rotated.set(self.height - 1 - y, x, self.get(x, y));
+            }
+        }
+        rotated
+    }
+
+    /// Mirror along the vertical axis, left becomes right.
+    pub fn flip_horizontal(&self) -> Pattern {
+        let mut flipped = Pattern::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                flipped.set(self.width - 1 - x, y, self.get(x, y));
+            }
+        }
+        flipped
+    }
+
+    /// Mirror along the horizontal axis, top becomes bottom.
+    pub fn flip_vertical(&self) -> Pattern {
+        let mut flipped = Pattern::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                flipped.set(x, self.height - 1 - y, self.get(x, y));
+            }
+        }
+        flipped
+    }
+
+    // RLE
+    // https://conwaylife.com/wiki/Run_Length_Encoded
+    pub fn to_rle(&self) -> String {
+        let mut tags: Vec<(usize, char)> = Vec::new();
+        let mut push = |count: usize, tag: char| {
+            if count == 0 {
+                return;
+            }
+            match tags.last_mut() {
+                Some((last_count, last_tag)) if *last_tag == tag => *last_count += count,
+                _ => tags.push((count, tag)),
+            }
+        };
+
+        let mut current_line = 0;
+        for y in 0..self.height {
+            // Dead cells at the end of a line are implied
+            let Some(last_alive) = (0..self.width).rev().find(|x| self.get(*x, y)) else {
+                continue;
+            };
+            push(y - current_line, '$');
+            current_line = y;
+
+            for x in 0..=last_alive {
+                push(1, if self.get(x, y) { 'o' } else { 'b' });
+            }
+        }
+        push(1, '!');
+
+        let mut rle = format!("x = {}, y = {}, rule = B3/S23\n", self.width, self.height);
+        let mut line = String::new();
+        for (count, tag) in tags {
+            let item = match count {
+                1 => tag.to_string(),
+                _ => format!("{count}{tag}"),
+            };
+            if line.len() + item.len() > RLE_LINE_LENGTH {
+                rle.push_str(&line);
+                rle.push('\n');
+                line.clear();
+            }
+            line.push_str(&item);
+        }
+        rle.push_str(&line);
+        rle.push('\n');
+
+        rle
+    }
+
+    /// Reads a pattern that fits in a grid of `grid` width and height, the text comes from
+    /// the clipboard so anything bigger is refused before it is allocated.
+    pub fn from_rle(text: &str, grid: (usize, usize)) -> Result<Pattern, Error> {
+        let too_big = || {
+            Error::Pattern(format!("the pattern doesn't fit in {}x{}", grid.0, grid.1))
+        };
+        let mut size: Option<(usize, usize)> = None;
+        let mut rows: Vec<Vec<bool>> = vec![Vec::new()];
+        let mut count: Option<usize> = None;
+
+        'lines: for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('x') {
+                let (width, height) = parse_header(line)?;
+                if width > grid.0 || height > grid.1 {
+                    return Err(too_big());
+                }
+                size = Some((width, height));
+                continue;
+            }
+
+            for character in line.chars() {
+                match character {
+                    '0'..='9' => {
+                        let digit = character as usize - '0' as usize;
+                        count = count
+                            .unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|count| count.checked_add(digit));
+                        if count.is_none_or(|count| count > grid.0.max(grid.1)) {
+                            return Err(too_big());
+                        }
+                    }
+                    '$' => {
+                        let count = count.take().unwrap_or(1);
+                        if rows.len() + count > grid.1 {
+                            return Err(too_big());
+                        }
+                        for _ in 0..count {
+                            rows.push(Vec::new());
+                        }
+                    }
+                    '!' => break 'lines,
+                    // `b` and `.` are dead cells, any other letter is an alive one
+                    character if character.is_ascii_alphabetic() || character == '.' => {
+                        let row = rows.last_mut().unwrap();
+                        let count = count.take().unwrap_or(1);
+                        if row.len() + count > grid.0 {
+                            return Err(too_big());
+                        }
+                        let alive = !matches!(character, 'b' | '.');
+                        row.extend(std::iter::repeat_n(alive, count));
+                    }
+                    character if character.is_whitespace() => (),
+                    character => {
+                        return Err(Error::Pattern(format!("unexpected `{character}` in RLE")))
+                    }
+                }
+            }
+        }
+
+        let (width, height) = size.unwrap_or_else(|| {
+            let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+            (width, rows.len())
+        });
+        if rows.len() > height || rows.iter().any(|row| row.len() > width) {
+            return Err(Error::Pattern(String::from(
+                "the RLE pattern is bigger than its header",
+            )));
+        }
+
+        let mut pattern = Pattern::new(width, height);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, alive) in row.iter().enumerate() {
+                pattern.set(x, y, *alive);
+            }
+        }
+
+        Ok(pattern)
+    }
+    // RLE END
+}
+
+// Reads `x = 3, y = 2, rule = B3/S23`, the rule is ignored
+fn parse_header(line: &str) -> Result<(usize, usize), Error> {
+    let mut width = None;
+    let mut height = None;
+
+    for field in line.split(',') {
+        let Some((name, value)) = field.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().parse::<usize>();
+        match (name.trim(), value) {
+            ("x", Ok(value)) => width = Some(value),
+            ("y", Ok(value)) => height = Some(value),
+            ("x" | "y", Err(_)) => {
+                return Err(Error::Pattern(format!("invalid RLE header `{line}`")))
+            }
+            _ => (),
+        }
+    }
+
+    match (width, height) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(Error::Pattern(format!("invalid RLE header `{line}`"))),
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const GRID: (usize, usize) = (40, 30);
+
+    fn glider() -> Pattern {
+        Pattern::from_rle("x = 3, y = 3\nbo$2bo$3o!", GRID).unwrap()
+    }
+
+    #[test]
+    fn test_from_rle() {
+        let glider = glider();
+        assert_eq!((glider.width, glider.height), (3, 3));
+        assert_eq!(
+            glider.cells,
+            vec![false, true, false, false, false, true, true, true, true]
+        );
+    }
+
+    #[test]
+    fn test_to_rle() {
+        assert_eq!(glider().to_rle(), "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+
+        let mut spaced = Pattern::new(2, 4);
+        spaced.set(0, 0, true);
+        spaced.set(1, 3, true);
+        assert_eq!(spaced.to_rle(), "x = 2, y = 4, rule = B3/S23\no3$bo!\n");
+    }
+
+    #[test]
+    fn test_invalid_rle() {
+        assert!(matches!(Pattern::from_rle("x = 1, y = 1\n3o!", GRID), Err(Error::Pattern(_))));
+        assert!(matches!(Pattern::from_rle("x = a, y = 1\no!", GRID), Err(Error::Pattern(_))));
+        assert!(matches!(Pattern::from_rle("o?o!", GRID), Err(Error::Pattern(_))));
+    }
+
+    #[test]
+    fn test_rle_bigger_than_the_grid() {
+        for rle in [
+            "x = 100000, y = 100000\no!",
+            "x = 41, y = 1\no!",
+            "99999999999o!",
+            "99999999999999999999999999o!",
+            "41o!",
+            "30$o!",
+        ] {
+            assert!(matches!(Pattern::from_rle(rle, GRID), Err(Error::Pattern(_))), "{rle}");
+        }
+        assert_eq!(Pattern::from_rle("40o29$o!", GRID).unwrap().height, 30);
+    }
+
+    #[test]
+    fn test_rotate_and_flip() {
+        let glider = glider();
+        assert_eq!(glider.rotate().to_rle(), "x = 3, y = 3, rule = B3/S23\no$obo$2o!\n");
+        assert_eq!(glider.flip_horizontal().to_rle(), "x = 3, y = 3, rule = B3/S23\nbo$o$3o!\n");
+        assert_eq!(glider.flip_vertical().to_rle(), "x = 3, y = 3, rule = B3/S23\n3o$2bo$bo!\n");
+    }
+
+    proptest! {
+        #[test]
+        fn test_rle_round_trip(width in 1usize..20, height in 1usize..20, seed in any::<u64>()) {
+            let mut pattern = Pattern::new(width, height);
+            for (index, cell) in pattern.cells.iter_mut().enumerate() {
+                *cell = (seed >> (index % 64)) & 1 == 1;
+            }
+            prop_assert_eq!(Pattern::from_rle(&pattern.to_rle(), GRID).unwrap(), pattern.clone());
+            prop_assert_eq!(pattern.rotate().rotate().rotate().rotate(), pattern);
+        }
+    }
+}
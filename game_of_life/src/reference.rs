@@ -0,0 +1,187 @@
+// REFERENCE ENGINE
+// A slow and obvious step function, written straight from the rules, that the real
+// engines are checked against.
+
+use crate::Topology;
+
+pub type Grid = Vec<Vec<bool>>;
+
+fn alive_neighbours(grid: &Grid, x: usize, y: usize, topology: Topology) -> usize {
+    let height = grid.len() as isize;
+    let width = grid[0].len() as isize;
+    let mut alive = 0;
+
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if (dx, dy) == (0, 0) {
+                continue;
+            }
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            let (nx, ny) = match topology {
+                Topology::Bounded => (nx, ny),
+                Topology::Torus => (nx.rem_euclid(width), ny.rem_euclid(height)),
+            };
+            if nx >= 0 && nx < width && ny >= 0 && ny < height && grid[ny as usize][nx as usize] {
+                alive += 1;
+            }
+        }
+    }
+
+    alive
+}
+
+pub fn step(grid: &Grid, topology: Topology) -> Grid {
+    let mut next = grid.clone();
+
+    for (y, line) in grid.iter().enumerate() {
+        for (x, alive) in line.iter().enumerate() {
+            next[y][x] = matches!(
+                (alive, alive_neighbours(grid, x, y, topology)),
+                (true, 2) | (true, 3) | (false, 3)
+            );
+        }
+    }
+
+    next
+}
+// REFERENCE ENGINE END
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::World;
+    use proptest::prelude::*;
+    use web_time::Instant;
+    use window_rs::WindowBuffer;
+
+    const ALIVE: u32 = 0x0066CC33;
+
+    fn world_from_grid(grid: &Grid, topology: Topology) -> World {
+        let mut world = World::new(
+            WindowBuffer::new(grid[0].len(), grid.len()),
+            0,
+            Instant::now(),
+            0,
+            ALIVE,
+        );
+        world.topology = topology;
+        for (y, line) in grid.iter().enumerate() {
+            for (x, alive) in line.iter().enumerate() {
+                if *alive {
+                    world.window_buffer[(x, y)] = ALIVE;
+                }
+            }
+        }
+        world
+    }
+
+    fn grid_from_world(world: &World) -> Grid {
+        (0..world.window_buffer.height())
+            .map(|y| {
+                (0..world.window_buffer.width())
+                    .map(|x| world.window_buffer[(x, y)] == ALIVE)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Puts the pattern, given as lines of `#` and `.`, with its top left corner at (left, top).
+    fn place(pattern: &[&str], width: usize, height: usize, left: usize, top: usize) -> Grid {
+        let mut grid = vec![vec![false; width]; height];
+        for (y, line) in pattern.iter().enumerate() {
+            for (x, cell) in line.chars().enumerate() {
+                grid[(top + y) % height][(left + x) % width] = cell == '#';
+            }
+        }
+        grid
+    }
+
+    fn topology() -> impl Strategy<Value = Topology> {
+        prop_oneof![Just(Topology::Bounded), Just(Topology::Torus)]
+    }
+
+    fn grid() -> impl Strategy<Value = Grid> {
+        (1usize..16, 1usize..16).prop_flat_map(|(width, height)| {
+            prop::collection::vec(prop::collection::vec(any::<bool>(), width), height)
+        })
+    }
+
+    const STILL_LIFES: [&[&str]; 5] = [
+        &["##", "##"],
+        &[".##.", "#..#", ".##."],
+        &[".##.", "#..#", ".#.#", "..#."],
+        &["##.", "#.#", ".#."],
+        &[".#.", "#.#", ".#."],
+    ];
+
+    // (pattern, period)
+    const OSCILLATORS: [(&[&str], usize); 4] = [
+        (&["###"], 2),
+        (&[".###", "###."], 2),
+        (&["##..", "##..", "..##", "..##"], 2),
+        (&["..#....#..", "##.####.##", "..#....#.."], 15),
+    ];
+
+    const GLIDER: [&str; 3] = [".#.", "..#", "###"];
+
+    proptest! {
+        #[test]
+        fn engine_agrees_with_reference(grid in grid(), topology in topology(), steps in 1usize..5) {
+            let mut world = world_from_grid(&grid, topology);
+            let mut expected = grid;
+            for _ in 0..steps {
+                world.update();
+                expected = step(&expected, topology);
+                prop_assert_eq!(grid_from_world(&world), expected.clone());
+            }
+        }
+
+        #[test]
+        fn still_lifes_are_fixed_points(
+            index in 0..STILL_LIFES.len(),
+            topology in topology(),
+            (left, top) in (1usize..10, 1usize..10),
+        ) {
+            // One dead cell of margin on every side so that nothing touches the edges
+            let grid = place(STILL_LIFES[index], 16, 16, left, top);
+            let mut world = world_from_grid(&grid, topology);
+            world.update();
+            prop_assert_eq!(grid_from_world(&world), grid);
+        }
+
+        #[test]
+        fn oscillators_come_back_after_their_period(
+            index in 0..OSCILLATORS.len(),
+            topology in topology(),
+            (left, top) in (1usize..6, 1usize..6),
+        ) {
+            let (pattern, period) = OSCILLATORS[index];
+            let grid = place(pattern, 32, 32, left + 8, top + 8);
+            let mut world = world_from_grid(&grid, topology);
+            for generation in 1..=period {
+                world.update();
+                if generation < period {
+                    prop_assert_ne!(grid_from_world(&world), grid.clone());
+                }
+            }
+            prop_assert_eq!(grid_from_world(&world), grid);
+        }
+
+        #[test]
+        fn glider_moves_diagonally_on_a_torus(
+            (width, height) in (6usize..20, 6usize..20),
+            (left, top) in (0usize..20, 0usize..20),
+            periods in 1usize..8,
+        ) {
+            let grid = place(&GLIDER, width, height, left, top);
+            let mut world = world_from_grid(&grid, Topology::Torus);
+            for _ in 0..4 * periods {
+                world.update();
+            }
+            let expected = place(&GLIDER, width, height, left + periods, top + periods);
+            prop_assert_eq!(grid_from_world(&world), expected);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use web_time::{SystemTime, UNIX_EPOCH};
+use window_rs::WindowBuffer;
+
+use crate::{Error, World};
+
+// SAVE FORMAT
+// width (u64), height (u64), speed (u64), then every cell (u32) line by line, all big endian.
+// Sizes are u64 so that saves are the same on 32 bits targets like wasm.
+
+/// What a save file contains, read before being applied to a world.
+#[derive(Debug)]
+pub struct Save {
+    pub window_buffer: WindowBuffer,
+    pub speed: u64,
+}
+
+pub fn write<W: Write>(world: &World, mut save_file: W) -> Result<(), Error> {
+    save_file.write_all(&(world.window_buffer.width() as u64).to_be_bytes())?;
+    save_file.write_all(&(world.window_buffer.height() as u64).to_be_bytes())?;
+    save_file.write_all(&world.speed.to_be_bytes())?;
+
+    for number in &world.window_buffer.buffer() {
+        save_file.write_all(&number.to_be_bytes())?;
+    }
+
+    save_file.flush()?;
+
+    Ok(())
+}
+
+/// Reads a save made for a grid of `expected` width and height, a save of another size is
+/// refused before its cells are read.
+pub fn read<R: Read>(mut save_file: R, expected: (usize, usize)) -> Result<Save, Error> {
+    let truncated = |error: std::io::Error| match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => {
+            Error::Format(String::from("the file ends before the grid is complete"))
+        }
+        _ => Error::Io(error),
+    };
+
+    let mut saved_chunk: [u8; 8] = [0; 8];
+
+    save_file.read_exact(&mut saved_chunk).map_err(truncated)?;
+    let width = u64::from_be_bytes(saved_chunk) as usize;
+
+    save_file.read_exact(&mut saved_chunk).map_err(truncated)?;
+    let height = u64::from_be_bytes(saved_chunk) as usize;
+
+    save_file.read_exact(&mut saved_chunk).map_err(truncated)?;
+    let speed = u64::from_be_bytes(saved_chunk);
+
+    if (width, height) != expected {
+        return Err(Error::Dimension {
+            expected,
+            found: (width, height),
+        });
+    }
+
+    let mut window_buffer = WindowBuffer::new(width, height);
+
+    let mut saved_chunk_2: [u8; 4] = [0; 4];
+
+    for y in 0..window_buffer.height() {
+        for x in 0..window_buffer.width() {
+            save_file.read_exact(&mut saved_chunk_2).map_err(truncated)?;
+            window_buffer[(x, y)] = u32::from_be_bytes(saved_chunk_2)
+        }
+    }
+
+    Ok(Save {
+        window_buffer,
+        speed,
+    })
+}
+// SAVE FORMAT END
+
+pub fn save_to_path(world: &World, path: &Path) -> Result<(), Error> {
+    write(world, BufWriter::new(File::create(path)?))
+}
+
+pub fn load_from_path(path: &Path, expected: (usize, usize)) -> Result<Save, Error> {
+    read(BufReader::new(File::open(path)?), expected)
+}
+
+// FILE NAMES
+pub fn slot_path(save_dir: &Path, slot: u8) -> PathBuf {
+    save_dir.join(format!("slot-{slot}.save"))
+}
+
+pub fn autosave_path(save_dir: &Path, generation: u64) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default();
+
+    save_dir.join(format!("autosave-{timestamp}-{generation}.save"))
+}
+// FILE NAMES END
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use web_time::Instant;
+
+    #[test]
+    fn test_write_then_read() {
+        let mut world = World::new(WindowBuffer::new(3, 2), 0, Instant::now(), 4, 0x0066CC33);
+        world.window_buffer[(1, 0)] = world.colour_cell;
+        world.window_buffer[(2, 1)] = world.colour_cell;
+
+        let mut bytes = Vec::new();
+        write(&world, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 3 * 8 + 6 * 4);
+
+        let save = read(&bytes[..], (3, 2)).unwrap();
+        assert_eq!(save.speed, 4);
+        assert_eq!(save.window_buffer.to_string(), ".#.\n..#\n");
+
+        let error = read(&bytes[..], (2, 3)).unwrap_err();
+        assert!(matches!(error, Error::Dimension { .. }));
+    }
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(slot_path(Path::new("saves"), 3), Path::new("saves/slot-3.save"));
+
+        let autosave = autosave_path(Path::new("saves"), 42);
+        let name = autosave.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("autosave-"));
+        assert!(name.ends_with("-42.save"));
+    }
+}
@@ -0,0 +1,145 @@
+use rand::Rng;
+use window_rs::WindowBuffer;
+
+use crate::pattern::Pattern;
+
+/// A rectangle of the grid chosen with the mouse, both corners are included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Selection {
+    pub fn new(position: (usize, usize)) -> Self {
+        Self {
+            start: position,
+            end: position,
+        }
+    }
+
+    pub fn left(&self) -> usize {
+        self.start.0.min(self.end.0)
+    }
+
+    pub fn top(&self) -> usize {
+        self.start.1.min(self.end.1)
+    }
+
+    pub fn width(&self) -> usize {
+        self.start.0.abs_diff(self.end.0) + 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.start.1.abs_diff(self.end.1) + 1
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.left()..self.left() + self.width()).contains(&x)
+            && (self.top()..self.top() + self.height()).contains(&y)
+    }
+
+    /// Cells of the selection that are inside the buffer.
+    fn cells(&self, buffer: &WindowBuffer) -> impl Iterator<Item = (usize, usize)> {
+        let (left, top) = (self.left(), self.top());
+        let right = (left + self.width()).min(buffer.width());
+        let bottom = (top + self.height()).min(buffer.height());
+
+        (top..bottom).flat_map(move |y| (left..right).map(move |x| (x, y)))
+    }
+
+    pub fn copy(&self, buffer: &WindowBuffer, colour_cell: u32) -> Pattern {
+        let mut pattern = Pattern::new(self.width(), self.height());
+        for (x, y) in self.cells(buffer) {
+            pattern.set(x - self.left(), y - self.top(), buffer[(x, y)] == colour_cell);
+        }
+        pattern
+    }
+
+    pub fn clear(&self, buffer: &mut WindowBuffer) {
+        for (x, y) in self.cells(buffer).collect::<Vec<_>>() {
+            buffer[(x, y)] = 0;
+        }
+    }
+
+    /// Every cell of the selection gets a one in two chance to be alive.
+    pub fn fill_random<R: Rng>(&self, buffer: &mut WindowBuffer, colour_cell: u32, rng: &mut R) {
+        for (x, y) in self.cells(buffer).collect::<Vec<_>>() {
+            buffer[(x, y)] = if rng.gen_bool(0.5) { colour_cell } else { 0 };
+        }
+    }
+}
+
+/// Writes the alive cells of the pattern from `(left, top)`, like the "Or" paste mode of Golly.
+/// What falls outside of the grid is dropped. Returns the area covered by the pattern.
+pub fn paste(
+    pattern: &Pattern,
+    buffer: &mut WindowBuffer,
+    (left, top): (usize, usize),
+    colour_cell: u32,
+) -> Selection {
+    for y in 0..pattern.height {
+        for x in 0..pattern.width {
+            if pattern.get(x, y) && left + x < buffer.width() && top + y < buffer.height() {
+                buffer[(left + x, top + y)] = colour_cell;
+            }
+        }
+    }
+
+    Selection {
+        start: (left, top),
+        end: (
+            left + pattern.width.max(1) - 1,
+            top + pattern.height.max(1) - 1,
+        ),
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_selection_bounds() {
+        let selection = Selection {
+            start: (4, 1),
+            end: (2, 3),
+        };
+        assert_eq!((selection.left(), selection.top()), (2, 1));
+        assert_eq!((selection.width(), selection.height()), (3, 3));
+        assert!(selection.contains(4, 3));
+        assert!(!selection.contains(5, 3));
+    }
+
+    #[test]
+    fn test_copy_paste() {
+        let mut buffer = WindowBuffer::new(6, 4);
+        buffer[(1, 0)] = 1;
+        buffer[(2, 1)] = 1;
+        buffer[(0, 2)] = 1;
+        buffer[(1, 2)] = 1;
+        buffer[(2, 2)] = 1;
+
+        let selection = Selection {
+            start: (0, 0),
+            end: (2, 2),
+        };
+        let glider = selection.copy(&buffer, 1);
+        selection.clear(&mut buffer);
+
+        let pasted = paste(&glider.rotate(), &mut buffer, (4, 1), 1);
+        assert_eq!(pasted.end, (6, 3));
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        ......
+        ....#.
+        ....#.
+        ....##
+        "###
+        );
+    }
+}
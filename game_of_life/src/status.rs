@@ -0,0 +1,183 @@
+use web_time::{Duration, Instant};
+use window_rs::WindowBuffer;
+
+// A status message is shown on top of the grid for a few seconds
+pub const STATUS_DURATION: Duration = Duration::from_secs(3);
+pub const STATUS_COLOUR: u32 = 0x00FF3333;
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+#[derive(Debug)]
+pub struct Status {
+    pub message: String,
+    pub since: Instant,
+}
+
+impl Status {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            since: Instant::now(),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.since.elapsed() < STATUS_DURATION
+    }
+}
+
+// FONT
+// Each glyph is 5 rows of 3 bits, the leftmost pixel being the highest bit
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+// FONT END
+
+/// Splits the text in lines of at most `columns` characters, cutting between words when possible.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > columns {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+
+        while line.len() > columns && columns > 0 {
+            let rest = line.split_off(columns);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Writes the text from the top left corner of the buffer, on a black background
+/// so it stays readable over the cells. What doesn't fit is dropped.
+pub fn draw_text(buffer: &mut WindowBuffer, text: &str, colour: u32) {
+    let columns = (buffer.width() + 1) / (GLYPH_WIDTH + 1);
+    // Not even one character fits
+    if columns == 0 {
+        return;
+    }
+
+    for (line_index, line) in wrap(text, columns).iter().enumerate() {
+        let top = line_index * (GLYPH_HEIGHT + 1);
+        if top + GLYPH_HEIGHT > buffer.height() {
+            break;
+        }
+
+        for y in top..(top + GLYPH_HEIGHT + 1).min(buffer.height()) {
+            for x in 0..(line.len() * (GLYPH_WIDTH + 1)).min(buffer.width()) {
+                buffer[(x, y)] = 0;
+            }
+        }
+
+        for (character_index, character) in line.chars().enumerate() {
+            let left = character_index * (GLYPH_WIDTH + 1);
+            for (row, bits) in glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        buffer[(left + column, top + row)] = colour;
+                    }
+                }
+            }
+        }
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("BAD SIZE", 7), vec!["BAD", "SIZE"]);
+        assert_eq!(wrap("SAVE ERROR", 20), vec!["SAVE ERROR"]);
+        assert_eq!(wrap("ABCDEFGHIJ", 4), vec!["ABCD", "EFGH", "IJ"]);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut buffer = WindowBuffer::new(12, 12);
+        draw_text(&mut buffer, "ok go", STATUS_COLOUR);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .#..#.#.....
+        #.#.#.#.....
+        #.#.##......
+        #.#.#.#.....
+        .#..#.#.....
+        ............
+        .##..#......
+        #...#.#.....
+        #.#.#.#.....
+        #.#.#.#.....
+        .##..#......
+        ............
+        "###
+        );
+    }
+
+    #[test]
+    fn test_draw_text_too_narrow() {
+        let mut buffer = WindowBuffer::new(2, 12);
+        draw_text(&mut buffer, "SAVED", STATUS_COLOUR);
+        assert_eq!(buffer.to_string(), WindowBuffer::new(2, 12).to_string());
+    }
+}
@@ -0,0 +1,431 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use graphic::{Graphic, Key, Mouse};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::{Clamped, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, Event, HtmlCanvasElement, HtmlInputElement, ImageData,
+    KeyboardEvent, MouseEvent,
+};
+use pixel_sims::main_loop::{self, FixedTimestep};
+use pixel_sims::Simulation;
+use window_rs::WindowBuffer;
+
+use crate::{Cli, Game, Topology, World};
+
+//KEYBOARD MANAGEMENT
+// Names are the `KeyboardEvent.key` values of the browser
+pub fn key_name(key: Key) -> Option<&'static str> {
+    match key {
+        Key::Escape => Some("Escape"),
+        Key::Space => Some(" "),
+        Key::Up => Some("ArrowUp"),
+        Key::Down => Some("ArrowDown"),
+        Key::Quit => Some("q"),
+        Key::Save => Some("s"),
+        Key::L => Some("l"),
+        Key::C => Some("c"),
+        Key::X => Some("x"),
+        Key::V => Some("v"),
+        Key::R => Some("r"),
+        Key::F => Some("f"),
+        Key::U => Some("u"),
+        Key::N => Some("n"),
+        Key::Delete => Some("Delete"),
+        Key::Key1 => Some("1"),
+        Key::Key2 => Some("2"),
+        Key::Key3 => Some("3"),
+        Key::Key4 => Some("4"),
+        Key::Key5 => Some("5"),
+        Key::Key6 => Some("6"),
+        Key::Key7 => Some("7"),
+        Key::Key8 => Some("8"),
+        Key::Key9 => Some("9"),
+        _ => None,
+    }
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    match name {
+        "Escape" => Some(Key::Escape),
+        " " => Some(Key::Space),
+        "ArrowUp" => Some(Key::Up),
+        "ArrowDown" => Some(Key::Down),
+        "q" | "Q" => Some(Key::Quit),
+        "s" | "S" => Some(Key::Save),
+        "l" | "L" => Some(Key::L),
+        "c" | "C" => Some(Key::C),
+        "x" | "X" => Some(Key::X),
+        "v" | "V" => Some(Key::V),
+        "r" | "R" => Some(Key::R),
+        "f" | "F" => Some(Key::F),
+        "u" | "U" => Some(Key::U),
+        "n" | "N" => Some(Key::N),
+        "Delete" => Some(Key::Delete),
+        "1" => Some(Key::Key1),
+        "2" => Some(Key::Key2),
+        "3" => Some(Key::Key3),
+        "4" => Some(Key::Key4),
+        "5" => Some(Key::Key5),
+        "6" => Some(Key::Key6),
+        "7" => Some(Key::Key7),
+        "8" => Some(Key::Key8),
+        "9" => Some(Key::Key9),
+        _ => None,
+    }
+}
+//KEYBOARD MANAGEMENT END
+
+//COLOURS MANAGEMENT
+// The window buffer stores 0x00RRGGBB, the canvas wants RGBA bytes
+pub fn to_rgba(buffer: &WindowBuffer) -> Vec<u8> {
+    buffer
+        .buffer()
+        .iter()
+        .flat_map(|pixel| {
+            let [_, red, green, blue] = pixel.to_be_bytes();
+            [red, green, blue, u8::MAX]
+        })
+        .collect()
+}
+//COLOURS MANAGEMENT END
+
+// INPUT STATE
+// Filled by the DOM event listeners, read by the `Graphic` implementation.
+// `pressed` and `released` only live for one frame.
+#[derive(Debug, Default)]
+struct Input {
+    down: Vec<String>,
+    pressed: Vec<String>,
+    released: Vec<String>,
+    mouse_pos: Option<(f32, f32)>,
+    // Indexed by `MouseEvent.button`: left, middle, right
+    mouse_down: [bool; 3],
+}
+
+pub struct Canvas {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    input: Rc<RefCell<Input>>,
+}
+
+impl Canvas {
+    fn listen_keyboard(&self) {
+        let document = web_sys::window().unwrap().document().unwrap();
+
+        let input = self.input.clone();
+        let keydown = Closure::<dyn FnMut(_)>::new(move |event: KeyboardEvent| {
+            let mut input = input.borrow_mut();
+            let name = event.key();
+            if !input.down.contains(&name) {
+                input.pressed.push(name.clone());
+                input.down.push(name);
+            }
+        });
+        document
+            .add_event_listener_with_callback("keydown", keydown.as_ref().unchecked_ref())
+            .unwrap();
+        keydown.forget();
+
+        let input = self.input.clone();
+        let keyup = Closure::<dyn FnMut(_)>::new(move |event: KeyboardEvent| {
+            let mut input = input.borrow_mut();
+            let name = event.key();
+            input.down.retain(|key| *key != name);
+            input.released.push(name);
+        });
+        document
+            .add_event_listener_with_callback("keyup", keyup.as_ref().unchecked_ref())
+            .unwrap();
+        keyup.forget();
+    }
+
+    fn listen_mouse(&self) {
+        // The canvas is scaled by CSS, so the position is brought back to buffer coordinates
+        let input = self.input.clone();
+        let canvas = self.canvas.clone();
+        let mousemove = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            let x = event.offset_x() as f32 * canvas.width() as f32 / canvas.client_width() as f32;
+            let y =
+                event.offset_y() as f32 * canvas.height() as f32 / canvas.client_height() as f32;
+            input.borrow_mut().mouse_pos = Some((x, y));
+        });
+        self.canvas
+            .add_event_listener_with_callback("mousemove", mousemove.as_ref().unchecked_ref())
+            .unwrap();
+        mousemove.forget();
+
+        let input = self.input.clone();
+        let mouseleave = Closure::<dyn FnMut(_)>::new(move |_: MouseEvent| {
+            let mut input = input.borrow_mut();
+            input.mouse_pos = None;
+            input.mouse_down = [false; 3];
+        });
+        self.canvas
+            .add_event_listener_with_callback("mouseleave", mouseleave.as_ref().unchecked_ref())
+            .unwrap();
+        mouseleave.forget();
+
+        let input = self.input.clone();
+        let mousedown = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            if let Some(down) = input.borrow_mut().mouse_down.get_mut(event.button() as usize) {
+                *down = true;
+            }
+        });
+        self.canvas
+            .add_event_listener_with_callback("mousedown", mousedown.as_ref().unchecked_ref())
+            .unwrap();
+        mousedown.forget();
+
+        let input = self.input.clone();
+        let mouseup = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            if let Some(down) = input.borrow_mut().mouse_down.get_mut(event.button() as usize) {
+                *down = false;
+            }
+        });
+        self.canvas
+            .add_event_listener_with_callback("mouseup", mouseup.as_ref().unchecked_ref())
+            .unwrap();
+        mouseup.forget();
+
+        // The right button selects, the browser menu would get in the way
+        let contextmenu = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            event.prevent_default();
+        });
+        self.canvas
+            .add_event_listener_with_callback("contextmenu", contextmenu.as_ref().unchecked_ref())
+            .unwrap();
+        contextmenu.forget();
+    }
+}
+
+impl Graphic for Canvas {
+    fn new(name: &str, width: usize, height: usize) -> Self {
+        let document = web_sys::window().unwrap().document().unwrap();
+        document.set_title(name);
+
+        let canvas: HtmlCanvasElement = document
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        canvas.set_width(width as u32);
+        canvas.set_height(height as u32);
+        document.body().unwrap().append_child(&canvas).unwrap();
+
+        let context: CanvasRenderingContext2d = canvas
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+
+        let canvas = Canvas {
+            canvas,
+            context,
+            input: Rc::new(RefCell::new(Input::default())),
+        };
+        canvas.listen_keyboard();
+        canvas.listen_mouse();
+
+        canvas
+    }
+
+    fn is_open(&self) -> bool {
+        self.canvas.is_connected()
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        match key_name(key) {
+            Some(name) => self.input.borrow().down.iter().any(|key| key == name),
+            None => false,
+        }
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        match key_name(key) {
+            Some(name) => self.input.borrow().pressed.iter().any(|key| key == name),
+            None => false,
+        }
+    }
+
+    fn get_mouse_pos(&self, mouse: Mouse) -> Option<(f32, f32)> {
+        // The offset goes up to the width of the canvas included, like minifb the position is
+        // dropped or brought back in it
+        let (x, y) = self.input.borrow().mouse_pos?;
+        let (width, height) = (self.canvas.width() as f32, self.canvas.height() as f32);
+        let inside = (0.0..width).contains(&x) && (0.0..height).contains(&y);
+        match mouse {
+            Mouse::Discard if !inside => None,
+            _ => Some((x.min(width - 1.0).max(0.0), y.min(height - 1.0).max(0.0))),
+        }
+    }
+
+    fn get_mouse_down(&self, mouse: Mouse) -> bool {
+        match mouse {
+            Mouse::Left => self.input.borrow().mouse_down[0],
+            Mouse::Right => self.input.borrow().mouse_down[2],
+            _ => false,
+        }
+    }
+
+    fn get_keys_released(&self) -> Vec<Key> {
+        self.input
+            .borrow()
+            .released
+            .iter()
+            .filter_map(|name| key_from_name(name))
+            .collect()
+    }
+
+    fn update_with_buffer(&mut self, buffer: &WindowBuffer) {
+        if self.canvas.width() as usize != buffer.width()
+            || self.canvas.height() as usize != buffer.height()
+        {
+            self.canvas.set_width(buffer.width() as u32);
+            self.canvas.set_height(buffer.height() as u32);
+        }
+
+        let pixels = to_rgba(buffer);
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&pixels),
+            buffer.width() as u32,
+            buffer.height() as u32,
+        )
+        .unwrap();
+        self.context.put_image_data(&image, 0.0, 0.0).unwrap();
+
+        // One call per frame: the key presses of this frame have been handled
+        let mut input = self.input.borrow_mut();
+        input.pressed.clear();
+        input.released.clear();
+    }
+}
+// INPUT STATE END
+
+// ENTRY POINT
+fn request_animation_frame(callback: &Closure<dyn FnMut()>) {
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(callback.as_ref().unchecked_ref())
+        .unwrap();
+}
+
+fn listen_file_picker(game: Rc<RefCell<Game>>, file_input: HtmlInputElement) {
+    let picker = file_input.clone();
+    let change = Closure::<dyn FnMut(_)>::new(move |_: Event| {
+        let Some(file) = picker.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        let game = game.clone();
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let Ok(content) = JsFuture::from(file.array_buffer()).await else {
+                return;
+            };
+            let bytes = js_sys::Uint8Array::new(&content).to_vec();
+            let world = &mut game.borrow_mut().world;
+            if let Err(error) = world.load_save_file(&bytes[..]) {
+                world.set_status(error.status());
+            }
+        });
+    });
+    file_input
+        .add_event_listener_with_callback("change", change.as_ref().unchecked_ref())
+        .unwrap();
+    change.forget();
+}
+
+/// Starts the game in the page, patterns are loaded from the `<input type="file">`
+/// whose id is `file_input_id`.
+#[wasm_bindgen]
+pub fn start(width: usize, height: usize, file_input_id: &str) -> Result<(), JsValue> {
+    let cli = Cli {
+        width,
+        height,
+        file_path: None,
+        save_dir: String::from("."),
+        autosave: None,
+        save_on_exit: false,
+        topology: Topology::Bounded,
+    };
+
+    let world = World::from_cli(&cli).map_err(|error| JsValue::from_str(&error.to_string()))?;
+    let mut buffer = world.frame();
+    let game = Rc::new(RefCell::new(Game { world, cli }));
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    if let Some(file_input) = document.get_element_by_id(file_input_id) {
+        listen_file_picker(game.clone(), file_input.dyn_into()?);
+    }
+
+    let mut window = Canvas::new("Game Of Life", width, height);
+    let mut timestep = FixedTimestep::new(Simulation::<Canvas>::tick(&*game.borrow()));
+
+    let frame = Rc::new(RefCell::new(None::<Closure<dyn FnMut()>>));
+    let next_frame = frame.clone();
+
+    *frame.borrow_mut() = Some(Closure::new(move || {
+        // The browser calls us for every frame, so the loop of `main_loop::run` can't be used
+        main_loop::frame(&window, &mut *game.borrow_mut(), &mut timestep, &mut buffer);
+        window.update_with_buffer(&buffer);
+
+        if window.is_open() {
+            request_animation_frame(next_frame.borrow().as_ref().unwrap());
+        }
+    }));
+
+    request_animation_frame(frame.borrow().as_ref().unwrap());
+
+    Ok(())
+}
+// ENTRY POINT END
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_key_names() {
+        for name in ["Escape", " ", "ArrowUp", "ArrowDown", "q", "s", "l", "1", "9", "c", "Delete"] {
+            assert_eq!(key_from_name(name).and_then(key_name), Some(name));
+        }
+        assert!(key_from_name("Shift").is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_to_rgba() {
+        let mut buffer = WindowBuffer::new(2, 1);
+        buffer[(0, 0)] = 0x00_12_34_56;
+
+        assert_eq!(to_rgba(&buffer), vec![0x12, 0x34, 0x56, 0xff, 0, 0, 0, 0xff]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_load_save_file() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&3u64.to_be_bytes());
+        bytes.extend_from_slice(&2u64.to_be_bytes());
+        bytes.extend_from_slice(&5u64.to_be_bytes());
+        for cell in [0u32, 0x0066CC33, 0, 0x0066CC33, 0, 0] {
+            bytes.extend_from_slice(&cell.to_be_bytes());
+        }
+
+        let mut world = World::new(
+            WindowBuffer::new(3, 2),
+            0,
+            web_time::Instant::now(),
+            0,
+            0x0066CC33,
+        );
+        world.load_save_file(&bytes[..]).unwrap();
+
+        assert_eq!(world.speed, 5);
+        assert_eq!(world.window_buffer.to_string(), ".#.\n#..\n");
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use grains_de_sable::bench::Layout;
+use grains_de_sable::World;
+use window_rs::WindowBuffer;
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 360;
+const GRAINS: [usize; 3] = [1_000, 10_000, 100_000];
+const LAYOUTS: [Layout; 3] = [Layout::Column, Layout::Floor, Layout::Rain];
+// Two seconds of the game, the piles have started to settle and their chunks to sleep
+const WARM_UP_TICKS: usize = 120;
+
+// Every chunk of a new world is awake and has to be drawn. The ticks of the warm up aren't
+// timed, what is timed is the world as it is while the game runs.
+fn world(layout: Layout, grains: usize) -> World {
+    let mut world = World::new(WIDTH, HEIGHT, 0);
+    layout.build(&mut world, grains, 42);
+    for _ in 0..WARM_UP_TICKS {
+        world.update();
+    }
+    world
+}
+
+// The world goes on from one sample to the next, the way it does in the game
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    group.sample_size(20);
+    for layout in LAYOUTS {
+        for grains in GRAINS {
+            let mut world = world(layout, grains);
+            group.throughput(Throughput::Elements(grains as u64));
+            group.bench_function(BenchmarkId::new(layout.to_string(), grains), |b| {
+                b.iter(|| world.update())
+            });
+        }
+    }
+    group.finish();
+}
+
+// Only the chunks that changed are drawn again, so each display follows an update that
+// isn't timed
+fn display(c: &mut Criterion) {
+    let mut group = c.benchmark_group("display");
+    group.sample_size(20);
+    for layout in LAYOUTS {
+        for grains in GRAINS {
+            let mut world = world(layout, grains);
+            let mut buffer = WindowBuffer::new(WIDTH, HEIGHT);
+            world.display(&mut buffer);
+            group.throughput(Throughput::Elements(grains as u64));
+            group.bench_function(BenchmarkId::new(layout.to_string(), grains), |b| {
+                b.iter_custom(|iterations| {
+                    let mut time = Duration::ZERO;
+                    for _ in 0..iterations {
+                        world.update();
+                        let start = Instant::now();
+                        world.display(&mut buffer);
+                        time += start.elapsed();
+                    }
+                    time
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update, display);
+criterion_main!(benches);
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use window_rs::WindowBuffer;
+
+use crate::{Material, Sand, World};
+
+/// How the grains of a benchmark are spread over the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// A tall stack in the middle that collapses into a pile.
+    Column,
+    /// Packed at the bottom, nothing moves.
+    Floor,
+    /// Scattered all over the world, everything falls.
+    Rain,
+}
+
+impl Layout {
+    /// Adds `grains` grains of sand to the world, as many as fit.
+    pub fn build(self, world: &mut World, grains: usize, seed: u64) {
+        let (width, height) = (world.width(), world.height());
+        let grains = grains.min(width * height);
+        match self {
+            Layout::Column => {
+                let columns = grains.div_ceil(height);
+                let start = (width - columns) / 2;
+                for index in 0..grains {
+                    let (x, y) = (start + index / height, height - 1 - index % height);
+                    world.add(Sand::new(x, y, Material::Sand));
+                }
+            }
+            Layout::Floor => {
+                for index in 0..grains {
+                    let (x, y) = (index % width, height - 1 - index / width);
+                    world.add(Sand::new(x, y, Material::Sand));
+                }
+            }
+            Layout::Rain => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                // Only the free cells can take a grain, the world may already be partly full
+                let free = (0..width * height)
+                    .filter(|index| world.get(index % width, index / width).is_none())
+                    .count();
+                let grains = grains.min(free);
+                let mut added = 0;
+                while added < grains {
+                    let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+                    if world.get(x, y).is_none() {
+                        world.add(Sand::new(x, y, Material::Sand));
+                        added += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{self:?}").to_lowercase())
+    }
+}
+
+/// How long each frame took to update the world, and to draw it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameTimes {
+    pub update: Vec<Duration>,
+    pub display: Vec<Duration>,
+}
+
+impl FrameTimes {
+    /// Updates and draws the world `frames` times, as fast as it can, one tick per frame.
+    pub fn measure(world: &mut World, frames: usize) -> Self {
+        let mut buffer = WindowBuffer::new(world.width(), world.height());
+        let mut times = Self::default();
+        for _ in 0..frames {
+            let start = Instant::now();
+            world.update();
+            let updated = Instant::now();
+            world.display(&mut buffer);
+            times.update.push(updated - start);
+            times.display.push(updated.elapsed());
+        }
+        times
+    }
+}
+
+/// The time under which `percent` % of the frames took, the nearest one that was measured.
+pub fn percentile(times: &[Duration], percent: f64) -> Duration {
+    let mut sorted = times.to_vec();
+    sorted.sort();
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 100.0];
+
+// One line per step of the frame, with the percentiles in milliseconds
+impl fmt::Display for FrameTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frames: Vec<Duration> = self
+            .update
+            .iter()
+            .zip(&self.display)
+            .map(|(update, display)| *update + *display)
+            .collect();
+        writeln!(
+            f,
+            "{:<8}{:>11}{:>11}{:>11}{:>11}",
+            "", "p50", "p90", "p99", "max"
+        )?;
+        for (name, times) in [
+            ("update", &self.update),
+            ("display", &self.display),
+            ("frame", &frames),
+        ] {
+            write!(f, "{name:<8}")?;
+            for percent in PERCENTILES {
+                let millis = percentile(times, percent).as_secs_f64() * 1000.0;
+                write!(f, "{millis:>9.3}ms")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_layouts() {
+        for layout in Layout::value_variants() {
+            let mut world = World::new(64, 36, 0);
+            layout.build(&mut world, 500, 0);
+            assert_eq!(world.grain_count(), 500, "{layout}");
+        }
+
+        let mut world = World::new(10, 4, 0);
+        Layout::Column.build(&mut world, 100, 0);
+        assert_eq!(world.grain_count(), 40);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let times: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        assert_eq!(percentile(&times, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&times, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&times, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+
+        let times = FrameTimes {
+            update: times,
+            display: vec![Duration::from_micros(250); 100],
+        };
+        assert_snapshot!(times, @r###"
+                        p50        p90        p99        max
+        update     50.000ms   90.000ms   99.000ms  100.000ms
+        display     0.250ms    0.250ms    0.250ms    0.250ms
+        frame      50.250ms   90.250ms   99.250ms  100.250ms
+        "###);
+    }
+
+    #[test]
+    fn test_measure() {
+        let mut world = World::new(64, 36, 0);
+        Layout::Rain.build(&mut world, 500, 0);
+        let times = FrameTimes::measure(&mut world, 5);
+        assert_eq!(times.update.len(), 5);
+        assert_eq!(times.display.len(), 5);
+    }
+
+    #[test]
+    fn test_rain_on_a_full_world() {
+        let mut world = World::new(16, 8, 0);
+        Layout::Floor.build(&mut world, 100, 0);
+        Layout::Rain.build(&mut world, 100, 0);
+        assert_eq!(world.grain_count(), 16 * 8);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use crate::brush::{self, Shape};
+use crate::{Material, REST_VELOCITY};
+
+/// A solid box, or ball, that falls in one piece. Its cells are in the world like the
+/// other particles, so the sand piles up on it, and it lands on whatever isn't a fluid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Body {
+    /// Centre of the body.
+    pub x: isize,
+    pub y: isize,
+    /// A box or a ball.
+    pub shape: Shape,
+    /// Width, in cells.
+    pub size: usize,
+    /// Made of a material that stays where it is put, the others crumble as soon as the
+    /// body is dropped.
+    pub material: Material,
+    // Downwards, in cells per tick, like the grains
+    pub(crate) velocity: f32,
+    // How far the body is between two cells
+    pub(crate) offset: f32,
+}
+
+impl Body {
+    pub fn new(x: isize, y: isize, shape: Shape, size: usize, material: Material) -> Self {
+        Self {
+            x,
+            y,
+            shape,
+            size,
+            material,
+            velocity: REST_VELOCITY,
+            offset: 0.0,
+        }
+    }
+
+    /// The cells of the body, some of them can be outside of the world.
+    pub fn cells(&self) -> Vec<(isize, isize)> {
+        brush::footprint(self.shape, self.size, self.x, self.y)
+    }
+
+    /// The cells the body would take if it went one cell down, and doesn't take yet.
+    pub fn cells_below(&self) -> Vec<(isize, isize)> {
+        // Boxes and balls have no holes, only the bottom of each column moves into a new cell
+        let mut bottoms = BTreeMap::new();
+        for (x, y) in self.cells() {
+            let bottom = bottoms.entry(x).or_insert(y);
+            *bottom = y.max(*bottom);
+        }
+        bottoms.into_iter().map(|(x, y)| (x, y + 1)).collect()
+    }
+
+    /// The top cell of each column, the ones the body leaves when it goes one cell down. In
+    /// the same order as `cells_below`.
+    pub fn tops(&self) -> Vec<(isize, isize)> {
+        let mut tops = BTreeMap::new();
+        for (x, y) in self.cells() {
+            let top = tops.entry(x).or_insert(y);
+            *top = y.min(*top);
+        }
+        tops.into_iter().collect()
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cells_below() {
+        let square = Body::new(5, 5, Shape::Square, 3, Material::Wood);
+        assert_eq!(square.cells().len(), 9);
+        assert_eq!(square.cells_below(), vec![(4, 7), (5, 7), (6, 7)]);
+        assert_eq!(square.tops(), vec![(4, 4), (5, 4), (6, 4)]);
+
+        // A ball touches the ground with the bottom of its curve, the sides are higher
+        let ball = Body::new(10, 10, Shape::Circle, 6, Material::Stone);
+        let below = ball.cells_below();
+        let lowest = below.iter().map(|(_, y)| *y).max().unwrap();
+        assert_eq!(lowest, 13);
+        assert!(below.iter().any(|(_, y)| *y < lowest));
+        assert_eq!(below.len(), 6);
+    }
+}
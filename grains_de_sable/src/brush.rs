@@ -0,0 +1,296 @@
+use crate::{Material, CONVEYOR_SPEED, FAN_SPEED};
+
+pub const MAX_BRUSH_SIZE: usize = 64;
+// Share of the cells of the brush that get a grain in spray mode, on each tick
+pub const SPRAY_RATE: f64 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Square,
+    Circle,
+}
+
+/// What a stroke of the mouse draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    /// Paints under the mouse as it moves.
+    Free,
+    /// A line as thick as the brush, from where the button was pressed to where it was
+    /// released.
+    Line,
+    /// A filled rectangle between the corners where the button was pressed and released.
+    Rectangle,
+    /// A filled slope under the line from where the button was pressed to where it was
+    /// released.
+    Ramp,
+}
+
+/// Where the conveyors drawn by the brush push the particles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    /// A fan, the particles fly up and fall back.
+    Up,
+}
+
+impl Direction {
+    /// The speed given to the particle lying on the conveyor, (sideways, downwards).
+    pub fn push(&self) -> (f32, f32) {
+        match self {
+            Direction::Left => (-CONVEYOR_SPEED, 0.0),
+            Direction::Right => (CONVEYOR_SPEED, 0.0),
+            Direction::Up => (0.0, -FAN_SPEED),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Brush {
+    pub material: Material,
+    /// Width of the brush, in cells.
+    pub size: usize,
+    pub shape: Shape,
+    /// Scatter a few grains instead of filling the whole brush.
+    pub spray: bool,
+    pub tool: Tool,
+    pub direction: Direction,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            material: Material::Sand,
+            size: 4,
+            shape: Shape::Square,
+            spray: false,
+            tool: Tool::Free,
+            direction: Direction::Right,
+        }
+    }
+}
+
+impl Brush {
+    pub fn grow(&mut self) {
+        self.size = (self.size + 1).min(MAX_BRUSH_SIZE);
+    }
+
+    pub fn shrink(&mut self) {
+        self.size = self.size.saturating_sub(1).max(1);
+    }
+
+    pub fn toggle_shape(&mut self) {
+        self.shape = match self.shape {
+            Shape::Square => Shape::Circle,
+            Shape::Circle => Shape::Square,
+        };
+    }
+
+    pub fn next_tool(&mut self) {
+        self.tool = match self.tool {
+            Tool::Free => Tool::Line,
+            Tool::Line => Tool::Rectangle,
+            Tool::Rectangle => Tool::Ramp,
+            Tool::Ramp => Tool::Free,
+        };
+    }
+
+    pub fn rotate(&mut self) {
+        self.direction = match self.direction {
+            Direction::Right => Direction::Left,
+            Direction::Left => Direction::Up,
+            Direction::Up => Direction::Right,
+        };
+    }
+
+    /// The cells covered by the shape of the tool dragged from `from` to `to`. The free
+    /// tool draws the brush at both ends.
+    pub fn shape(&self, from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+        let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+        let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+        let mut cells: Vec<_> = match self.tool {
+            Tool::Free => [from, to]
+                .into_iter()
+                .flat_map(|(x, y)| self.cells(x, y))
+                .collect(),
+            Tool::Line => line(from, to)
+                .into_iter()
+                .flat_map(|(x, y)| self.cells(x, y))
+                .collect(),
+            Tool::Rectangle => (top..=bottom)
+                .flat_map(|y| (left..=right).map(move |x| (x, y)))
+                .collect(),
+            Tool::Ramp => line(from, to)
+                .into_iter()
+                .flat_map(|(x, y)| (y..=bottom).map(move |y| (x, y)))
+                .collect(),
+        };
+        cells.sort_unstable_by_key(|(x, y)| (*y, *x));
+        cells.dedup();
+        cells
+    }
+
+    /// The cells covered by the brush centred on (x, y). They can be outside of the world.
+    pub fn cells(&self, x: isize, y: isize) -> Vec<(isize, isize)> {
+        footprint(self.shape, self.size, x, y)
+    }
+}
+
+/// The cells of a square, or a disc, `size` cells wide and centred on (x, y). They can be
+/// outside of the world.
+pub fn footprint(shape: Shape, size: usize, x: isize, y: isize) -> Vec<(isize, isize)> {
+    let radius = size as f32 / 2.0;
+    let size = size as isize;
+    let start = -(size / 2);
+    // Between two cells when the size is even
+    let centre = start as f32 + (size - 1) as f32 / 2.0;
+
+    let mut cells = Vec::new();
+    for dy in start..start + size {
+        for dx in start..start + size {
+            let inside = match shape {
+                Shape::Square => true,
+                Shape::Circle => {
+                    let (distance_x, distance_y) = (dx as f32 - centre, dy as f32 - centre);
+                    distance_x * distance_x + distance_y * distance_y <= radius * radius
+                }
+            };
+            if inside {
+                cells.push((x + dx, y + dy));
+            }
+        }
+    }
+    cells
+}
+
+/// Every cell on the segment from `from` to `to`, both ends included, so that a fast stroke
+/// of the mouse doesn't leave holes.
+pub fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut error = dx + dy;
+    let (mut x, mut y) = from;
+
+    let mut cells = vec![(x, y)];
+    while (x, y) != to {
+        let double = 2 * error;
+        if double >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if double <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn draw(cells: &[(isize, isize)], width: isize, height: isize) -> String {
+        let mut drawing = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                drawing.push(if cells.contains(&(x, y)) { '#' } else { '.' });
+            }
+            drawing.push('\n');
+        }
+        drawing
+    }
+
+    #[test]
+    fn test_shapes() {
+        let mut brush = Brush {
+            size: 5,
+            ..Brush::default()
+        };
+        assert_snapshot!(draw(&brush.cells(3, 3), 7, 7), @r###"
+        .......
+        .#####.
+        .#####.
+        .#####.
+        .#####.
+        .#####.
+        .......
+        "###);
+
+        brush.toggle_shape();
+        assert_snapshot!(draw(&brush.cells(3, 3), 7, 7), @r###"
+        .......
+        ..###..
+        .#####.
+        .#####.
+        .#####.
+        ..###..
+        .......
+        "###);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut brush = Brush::default();
+        for _ in 0..10 {
+            brush.shrink();
+        }
+        assert_eq!(brush.cells(2, 2), vec![(2, 2)]);
+        for _ in 0..100 {
+            brush.grow();
+        }
+        assert_eq!(brush.size, MAX_BRUSH_SIZE);
+    }
+
+    #[test]
+    fn test_tools() {
+        let mut brush = Brush {
+            size: 1,
+            ..Brush::default()
+        };
+        brush.next_tool();
+        assert_eq!(brush.tool, Tool::Line);
+        assert_snapshot!(draw(&brush.shape((0, 0), (6, 2)), 7, 4), @r###"
+        ##.....
+        ..###..
+        .....##
+        .......
+        "###);
+
+        brush.next_tool();
+        assert_snapshot!(draw(&brush.shape((5, 2), (1, 0)), 7, 4), @r###"
+        .#####.
+        .#####.
+        .#####.
+        .......
+        "###);
+
+        brush.next_tool();
+        assert_snapshot!(draw(&brush.shape((0, 0), (6, 3)), 7, 4), @r###"
+        #......
+        ###....
+        #####..
+        #######
+        "###);
+
+        brush.next_tool();
+        assert_eq!(brush.tool, Tool::Free);
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(line((0, 0), (0, 0)), vec![(0, 0)]);
+        assert_snapshot!(draw(&line((0, 0), (6, 2)), 7, 3), @r###"
+        ##.....
+        ..###..
+        .....##
+        "###);
+        assert_eq!(line((3, 1), (0, 1)), vec![(3, 1), (2, 1), (1, 1), (0, 1)]);
+    }
+}
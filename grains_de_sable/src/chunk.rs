@@ -0,0 +1,143 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Side of the square chunks the world is split in.
+pub const CHUNK_SIZE: usize = 64;
+
+// Chunks updated at the same time are one chunk apart, in a checkerboard. Each pass updates
+// a quarter of the chunks, in parallel.
+pub const PASSES: [(usize, usize); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+/// Which parts of the world need to be updated, and drawn again.
+#[derive(Debug)]
+pub struct Chunks {
+    columns: usize,
+    rows: usize,
+    width: usize,
+    height: usize,
+    // Updated on this tick
+    awake: Vec<bool>,
+    // Something changed in them or next to them, they are updated on the next tick
+    wake: Vec<AtomicBool>,
+    // Changed since the world was last displayed
+    dirty: Vec<AtomicBool>,
+}
+
+impl Chunks {
+    /// Every chunk starts awake and has to be drawn.
+    pub fn new(width: usize, height: usize) -> Self {
+        let (columns, rows) = (width.div_ceil(CHUNK_SIZE), height.div_ceil(CHUNK_SIZE));
+        Self {
+            columns,
+            rows,
+            width,
+            height,
+            awake: vec![true; columns * rows],
+            wake: (0..columns * rows)
+                .map(|_| AtomicBool::new(false))
+                .collect(),
+            dirty: (0..columns * rows).map(|_| AtomicBool::new(true)).collect(),
+        }
+    }
+
+    /// Something changed at (x, y): its chunk, and the chunks of the cells around it, are
+    /// updated on the next tick and drawn again.
+    pub fn touch(&self, x: usize, y: usize) {
+        let columns = x.saturating_sub(1) / CHUNK_SIZE..=(x + 1).min(self.width - 1) / CHUNK_SIZE;
+        let rows = y.saturating_sub(1) / CHUNK_SIZE..=(y + 1).min(self.height - 1) / CHUNK_SIZE;
+        for row in rows {
+            for column in columns.clone() {
+                let index = row * self.columns + column;
+                // Most of the time the chunk is already awake, reading the flag first
+                // saves the threads from fighting over the cache line
+                for flag in [&self.wake[index], &self.dirty[index]] {
+                    if !flag.load(Ordering::Relaxed) {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The awake chunks of a pass, as (column, row).
+    pub fn in_pass(&self, (column_parity, row_parity): (usize, usize)) -> Vec<(usize, usize)> {
+        let mut chunks = Vec::new();
+        // Bottom up, like the lines of a chunk
+        for row in (row_parity..self.rows).step_by(2).rev() {
+            for column in (column_parity..self.columns).step_by(2) {
+                if self.awake[row * self.columns + column] {
+                    chunks.push((column, row));
+                }
+            }
+        }
+        chunks
+    }
+
+    /// The chunks that were touched on the last tick wake up, the others fall asleep.
+    pub fn next_tick(&mut self) {
+        for (awake, wake) in self.awake.iter_mut().zip(&self.wake) {
+            *awake = wake.swap(false, Ordering::Relaxed);
+        }
+    }
+
+    /// The chunks to draw again, they are now clean.
+    pub fn take_dirty(&self) -> Vec<(usize, usize)> {
+        let mut chunks = Vec::new();
+        for (index, dirty) in self.dirty.iter().enumerate() {
+            if dirty.swap(false, Ordering::Relaxed) {
+                chunks.push((index % self.columns, index / self.columns));
+            }
+        }
+        chunks
+    }
+
+    /// The cells of a chunk, cut at the border of the world.
+    pub fn bounds(&self, (column, row): (usize, usize)) -> (Range<usize>, Range<usize>) {
+        let x = column * CHUNK_SIZE..((column + 1) * CHUNK_SIZE).min(self.width);
+        let y = row * CHUNK_SIZE..((row + 1) * CHUNK_SIZE).min(self.height);
+        (x, y)
+    }
+
+    pub fn awake_count(&self) -> usize {
+        self.awake.iter().filter(|awake| **awake).count()
+    }
+
+    pub fn index(&self, (column, row): (usize, usize)) -> usize {
+        row * self.columns + column
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_touch_wakes_the_neighbours_on_the_borders() {
+        let mut chunks = Chunks::new(200, 100);
+        chunks.next_tick();
+        chunks.take_dirty();
+        assert_eq!(chunks.awake_count(), 0);
+
+        chunks.touch(10, 10);
+        chunks.next_tick();
+        assert_eq!(chunks.awake_count(), 1);
+
+        chunks.touch(64, 63);
+        chunks.next_tick();
+        assert_eq!(chunks.in_pass((0, 0)), vec![(0, 0)]);
+        assert_eq!(chunks.in_pass((1, 0)), vec![(1, 0)]);
+        assert_eq!(chunks.in_pass((0, 1)), vec![(0, 1)]);
+        assert_eq!(chunks.in_pass((1, 1)), vec![(1, 1)]);
+        assert_eq!(chunks.take_dirty().len(), 4);
+        assert_eq!(chunks.take_dirty(), vec![]);
+    }
+
+    #[test]
+    fn test_bounds_are_cut_at_the_border() {
+        let chunks = Chunks::new(200, 100);
+        assert_eq!(chunks.bounds((0, 0)), (0..64, 0..64));
+        assert_eq!(chunks.bounds((3, 1)), (192..200, 64..100));
+    }
+}
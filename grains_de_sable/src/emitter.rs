@@ -0,0 +1,95 @@
+use crate::{Error, Material, ReactionTable};
+
+/// Pours grains of a material from a spout, at a fixed rate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Emitter {
+    /// Middle of the spout.
+    pub x: usize,
+    pub y: usize,
+    pub material: Material,
+    /// Chance for each cell of the spout to let a grain out on each tick.
+    pub rate: f64,
+    /// Width of the spout, in cells.
+    pub width: usize,
+}
+
+impl Emitter {
+    pub fn new(x: usize, y: usize, material: Material) -> Self {
+        Self {
+            x,
+            y,
+            material,
+            rate: 0.5,
+            width: 3,
+        }
+    }
+
+    /// The cells of the spout, some of them can be outside of the world.
+    pub fn spout(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let start = self.x.saturating_sub(self.width / 2);
+        (start..start + self.width).map(|x| (x, self.y))
+    }
+
+    /// `x,y[,material[,rate[,width]]]`, for example `320,10,water,0.2`, the material is one
+    /// of `reactions`.
+    pub fn parse(text: &str, reactions: &ReactionTable) -> Result<Self, Error> {
+        let invalid = || Error::Format(format!("`{text}` is not an emitter, try `x,y,sand,0.5,3`"));
+        let mut values = text.split(',').map(str::trim);
+
+        let x = values
+            .next()
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(invalid)?;
+        let y = values
+            .next()
+            .and_then(|y| y.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut emitter = Emitter::new(x, y, Material::Sand);
+        if let Some(material) = values.next() {
+            emitter.material = reactions.material(material)?;
+        }
+        if let Some(rate) = values.next() {
+            emitter.rate = rate
+                .parse()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(invalid)?;
+        }
+        if let Some(width) = values.next() {
+            emitter.width = width.parse().map_err(|_| invalid())?;
+        }
+        if values.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(emitter)
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_parse() {
+        let table = ReactionTable::default();
+        let parse = |text: &str| Emitter::parse(text, &table);
+        assert_eq!(parse("10,2").unwrap(), Emitter::new(10, 2, Material::Sand));
+        let emitter = parse("10, 2, water, 0.25, 5").unwrap();
+        assert_eq!(
+            (emitter.material, emitter.rate, emitter.width),
+            (Material::Water, 0.25, 5)
+        );
+        assert_eq!(
+            emitter.spout().collect::<Vec<_>>(),
+            vec![(8, 2), (9, 2), (10, 2), (11, 2), (12, 2)]
+        );
+
+        assert_snapshot!(parse("10").unwrap_err(), @"invalid file: `10` is not an emitter, try `x,y,sand,0.5,3`");
+        assert_snapshot!(parse("10,2,mud").unwrap_err(), @"invalid file: `mud` is not a material");
+        assert_snapshot!(parse("10,2,sand,2").unwrap_err(), @"invalid file: `10,2,sand,2` is not an emitter, try `x,y,sand,0.5,3`");
+    }
+}
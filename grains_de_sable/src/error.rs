@@ -0,0 +1,78 @@
+use std::fmt;
+use std::process::ExitCode;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A save file, an image, a recording or a reaction table can't be read.
+    Format(String),
+    /// A world was made for another window size.
+    Dimension {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl Error {
+    // Codes from sysexits.h
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::Io(_) => ExitCode::from(74),
+            Error::Format(_) | Error::Dimension { .. } => ExitCode::from(65),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "input/output error: {error}"),
+            Error::Format(reason) => write!(f, "invalid file: {reason}"),
+            Error::Dimension { expected, found } => write!(
+                f,
+                "the world is {}x{} but the window is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Format(error.to_string())
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(error: png::DecodingError) -> Self {
+        match error {
+            png::DecodingError::IoError(error) => Error::Io(error),
+            error => Error::Format(error.to_string()),
+        }
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(error: png::EncodingError) -> Self {
+        match error {
+            png::EncodingError::IoError(error) => Error::Io(error),
+            error => Error::Format(error.to_string()),
+        }
+    }
+}
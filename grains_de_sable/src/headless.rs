@@ -0,0 +1,265 @@
+use graphic::{Graphic, Key, Mouse};
+use window_rs::WindowBuffer;
+
+use crate::GameWindow;
+
+/// A window that is never shown, for the tests. They say what the mouse and the keyboard
+/// do on each frame, and read what would have been drawn.
+#[derive(Debug)]
+pub struct Headless {
+    pub open: bool,
+    pub mouse: Option<(f32, f32)>,
+    pub left: bool,
+    pub right: bool,
+    /// Keys held down.
+    pub down: Vec<Key>,
+    /// Keys pressed on this frame only.
+    pub pressed: Vec<Key>,
+    /// Keys released on this frame only.
+    pub released: Vec<Key>,
+    /// The wheel on this frame only.
+    pub scroll: f32,
+    /// Copy of the last buffer shown.
+    pub shown: WindowBuffer,
+    pub title: String,
+}
+
+impl Headless {
+    /// Holds the left button down over (x, y).
+    pub fn click(&mut self, x: f32, y: f32) {
+        self.mouse = Some((x, y));
+        self.left = true;
+    }
+
+    pub fn release_mouse(&mut self) {
+        self.left = false;
+        self.right = false;
+    }
+
+    /// Presses the key for one frame.
+    pub fn press(&mut self, key: Key) {
+        self.pressed.push(key);
+        self.down.push(key);
+    }
+
+    /// Forgets the keys pressed and released and the wheel of the last frame, the keys are
+    /// still held down.
+    pub fn next_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.scroll = 0.0;
+    }
+}
+
+impl Graphic for Headless {
+    fn new(name: &str, width: usize, height: usize) -> Self {
+        Self {
+            open: true,
+            mouse: None,
+            left: false,
+            right: false,
+            down: Vec::new(),
+            pressed: Vec::new(),
+            released: Vec::new(),
+            scroll: 0.0,
+            shown: WindowBuffer::new(width, height),
+            title: name.to_string(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.down.contains(&key)
+    }
+
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    fn get_mouse_pos(&self, _mouse: Mouse) -> Option<(f32, f32)> {
+        self.mouse
+    }
+
+    fn get_mouse_down(&self, mouse: Mouse) -> bool {
+        match mouse {
+            Mouse::Left => self.left,
+            Mouse::Right => self.right,
+            _ => false,
+        }
+    }
+
+    fn get_keys_released(&self) -> Vec<Key> {
+        self.released.clone()
+    }
+
+    fn update_with_buffer(&mut self, buffer: &WindowBuffer) {
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                self.shown[(x, y)] = buffer[(x, y)];
+            }
+        }
+    }
+}
+
+impl GameWindow for Headless {
+    fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{replay, Cli, Game, Material, Recorder, World};
+    use clap::Parser;
+    use pixel_sims::main_loop;
+    use pixel_sims::Simulation;
+
+    #[test]
+    fn test_click_drops_grains() {
+        let mut window = Headless::new("test", 6, 5);
+        let mut world = World::new(6, 5, 0);
+
+        window.press(Key::LeftBracket);
+        window.press(Key::Key3);
+        window.click(2.0, 1.0);
+        world.handle_user_input(&window);
+        window.next_frame();
+        assert_eq!(world.brush().size, 3);
+        assert_eq!(world.get(2, 1), Some(Material::Stone));
+
+        window.mouse = Some((4.0, 1.0));
+        world.handle_user_input(&window);
+        assert_eq!(world.grain_count(), 15);
+
+        window.release_mouse();
+        world.handle_user_input(&window);
+        window.right = true;
+        window.mouse = Some((2.0, 1.0));
+        world.handle_user_input(&window);
+        assert_eq!(world.grain_count(), 6);
+
+        // The wheel sizes the brush like the brackets
+        window.release_mouse();
+        window.scroll = 2.0;
+        world.handle_user_input(&window);
+        window.next_frame();
+        world.handle_user_input(&window);
+        assert_eq!(world.brush().size, 4);
+    }
+
+    #[test]
+    fn test_game_in_the_main_loop() {
+        let mut window = Headless::new("test", 5, 4);
+        let mut buffer = WindowBuffer::new(5, 4);
+        let cli = Cli::parse_from(["grains_de_sable"]);
+        let mut game = Game::new(World::new(5, 4, 0), cli, None);
+
+        window.click(2.0, 0.0);
+        let mut frames = 0;
+        main_loop::run(&mut window, &mut game, &mut buffer, |window, buffer| {
+            window.update_with_buffer(buffer);
+            window.release_mouse();
+            frames += 1;
+            frames < 3
+        });
+
+        // The brush is clipped at the top, the grains it drew are shown wherever they fell
+        assert_eq!(game.world.grain_count(), 8);
+        assert_eq!(
+            window
+                .shown
+                .buffer()
+                .iter()
+                .filter(|colour| **colour != 0)
+                .count(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_time_controls() {
+        let mut window = Headless::new("test", 10, 8);
+        let cli = Cli::parse_from(["grains_de_sable", "--paused", "--scale", "2"]);
+        let mut game = Game::new(World::new(5, 4, 0), cli, None);
+
+        // The mouse is over the cell under the pixel
+        window.click(6.0, 1.0);
+        Simulation::<Headless>::handle_input(&mut game, &window);
+        window.release_mouse();
+        assert_eq!(game.world.get(3, 0), Some(Material::Sand));
+        Simulation::<Headless>::update(&mut game);
+        assert_eq!(game.world.get(3, 0), Some(Material::Sand));
+
+        window.press(Key::N);
+        Simulation::<Headless>::handle_input(&mut game, &window);
+        window.next_frame();
+        assert_eq!(game.world.get(3, 1), Some(Material::Sand));
+
+        let tick = Simulation::<Headless>::tick(&game);
+        window.press(Key::Equal);
+        window.press(Key::Space);
+        Simulation::<Headless>::handle_input(&mut game, &window);
+        window.next_frame();
+        assert!(!game.paused);
+        assert_eq!(Simulation::<Headless>::tick(&game), tick / 2);
+        Simulation::<Headless>::update(&mut game);
+        assert_eq!(game.world.get(3, 2), Some(Material::Sand));
+
+        for _ in 0..10 {
+            window.press(Key::Minus);
+            Simulation::<Headless>::handle_input(&mut game, &window);
+            window.next_frame();
+        }
+        assert_eq!(game.speed, 1.0 / 16.0);
+    }
+
+    #[test]
+    fn test_speed_stops_at_the_most_ticks_per_frame() {
+        let mut window = Headless::new("test", 5, 4);
+        let cli = Cli::parse_from(["grains_de_sable", "--ticks-per-frame", "3"]);
+        let mut game = Game::new(World::new(5, 4, 0), cli, None);
+        for _ in 0..10 {
+            window.press(Key::Equal);
+            Simulation::<Headless>::handle_input(&mut game, &window);
+            window.next_frame();
+        }
+        // 3 ticks x2 is 6, x4 would be 12 and the main loop runs 8 at most
+        assert_eq!(game.speed, 2.0);
+    }
+
+    #[test]
+    fn test_loading_stops_the_recording() {
+        let dir = std::env::temp_dir();
+        let save = dir.join("grains_de_sable_test_load_while_recording.sand");
+        let recording = dir.join("grains_de_sable_test_load_while_recording.txt");
+        let cli = Cli::parse_from(["grains_de_sable", "--file-path", save.to_str().unwrap()]);
+        let recorder = Recorder::create(&recording).unwrap();
+        let mut game = Game::new(World::new(5, 4, 0), cli, Some(recorder));
+        let mut window = Headless::new("test", 5, 4);
+
+        // Saving doesn't change the world, the recording goes on
+        window.press(Key::Save);
+        Simulation::<Headless>::handle_input(&mut game, &window);
+        window.next_frame();
+        assert!(game.recorder.is_some());
+
+        window.press(Key::L);
+        Simulation::<Headless>::handle_input(&mut game, &window);
+        assert!(game.recorder.is_none());
+
+        // Neither key is in the recording
+        let frames = replay::load_from_path(&recording).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|frame| frame.input.keys.is_empty()));
+    }
+}
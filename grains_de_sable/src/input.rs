@@ -0,0 +1,178 @@
+use std::fmt;
+use std::str::FromStr;
+
+use graphic::{Key, Mouse};
+
+use crate::{
+    Error, GameWindow, BIGGER_BRUSH_KEY, BODY_KEY, CLEAR_KEY, EMITTER_KEY, NEXT_MATERIAL_KEY,
+    RAINBOW_KEY, ROTATE_KEY, SHAPE_KEY, SMALLER_BRUSH_KEY, SPRAY_KEY, TOOL_KEY,
+};
+
+// The keys that change the world. Saving and loading go to a file that a replay can't
+// count on, they aren't recorded, nor the keys that do nothing.
+const RECORDED_KEYS: [Key; 22] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::Key0,
+    Key::V,
+    NEXT_MATERIAL_KEY,
+    SMALLER_BRUSH_KEY,
+    BIGGER_BRUSH_KEY,
+    SHAPE_KEY,
+    SPRAY_KEY,
+    RAINBOW_KEY,
+    BODY_KEY,
+    CLEAR_KEY,
+    TOOL_KEY,
+    ROTATE_KEY,
+    EMITTER_KEY,
+];
+
+/// Everything the sim reads from the window on one frame, so that it can be recorded and
+/// played again without a window.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Input {
+    /// Cell under the mouse, `None` when the mouse is outside of the window.
+    pub mouse: Option<(isize, isize)>,
+    pub left: bool,
+    pub right: bool,
+    /// How far the mouse wheel turned, up is positive.
+    pub scroll: f32,
+    /// Keys pressed on this frame.
+    pub keys: Vec<Key>,
+}
+
+impl Input {
+    pub fn from_window(window: &impl GameWindow) -> Self {
+        Self {
+            mouse: window
+                .get_mouse_pos(Mouse::Discard)
+                .map(|(x, y)| (x as isize, y as isize)),
+            left: window.get_mouse_down(Mouse::Left),
+            right: window.get_mouse_down(Mouse::Right),
+            scroll: window.scroll(),
+            keys: RECORDED_KEYS
+                .into_iter()
+                .filter(|key| window.is_key_pressed(*key))
+                .collect(),
+        }
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+}
+
+// RECORDING FORMAT
+// One line per frame: the mouse position or `-` when it's outside of the window, the buttons
+// held down (`L`, `R`, `LR` or `-`), the mouse wheel, then the names of the keys pressed, all
+// separated by spaces. For example `120 45 L 0 Key2 RightBracket`.
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mouse {
+            Some((x, y)) => write!(f, "{x} {y}")?,
+            None => write!(f, "-")?,
+        }
+        let buttons = match (self.left, self.right) {
+            (false, false) => "-",
+            (true, false) => "L",
+            (false, true) => "R",
+            (true, true) => "LR",
+        };
+        write!(f, " {buttons} {}", self.scroll)?;
+        for key in &self.keys {
+            write!(f, " {key:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Input {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Error> {
+        let invalid = || Error::Format(format!("`{line}` is not a recorded frame"));
+        let mut words = line.split_whitespace();
+
+        let mouse = match words.next().ok_or_else(invalid)? {
+            "-" => None,
+            x => {
+                let y = words.next().ok_or_else(invalid)?;
+                Some((
+                    x.parse().map_err(|_| invalid())?,
+                    y.parse().map_err(|_| invalid())?,
+                ))
+            }
+        };
+        let (left, right) = match words.next().ok_or_else(invalid)? {
+            "-" => (false, false),
+            "L" => (true, false),
+            "R" => (false, true),
+            "LR" => (true, true),
+            _ => return Err(invalid()),
+        };
+        let scroll = words
+            .next()
+            .ok_or_else(invalid)?
+            .parse()
+            .map_err(|_| invalid())?;
+        let keys = words
+            .map(|name| {
+                RECORDED_KEYS
+                    .into_iter()
+                    .find(|key| format!("{key:?}") == name)
+                    .ok_or_else(|| Error::Format(format!("`{name}` is not a key")))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Input {
+            mouse,
+            left,
+            right,
+            scroll,
+            keys,
+        })
+    }
+}
+// RECORDING FORMAT END
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_write_then_parse() {
+        let input = Input {
+            mouse: Some((120, -3)),
+            left: true,
+            right: false,
+            scroll: -1.5,
+            keys: vec![Key::Key2, BIGGER_BRUSH_KEY],
+        };
+        assert_snapshot!(input, @"120 -3 L -1.5 Key2 RightBracket");
+        assert_eq!(input.to_string().parse::<Input>().unwrap(), input);
+
+        let outside = Input::default();
+        assert_snapshot!(outside, @"- - 0");
+        assert_eq!(outside.to_string().parse::<Input>().unwrap(), outside);
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert_snapshot!("".parse::<Input>().unwrap_err(), @"invalid file: `` is not a recorded frame");
+        assert_snapshot!("1 2 X".parse::<Input>().unwrap_err(), @"invalid file: `1 2 X` is not a recorded frame");
+        assert_snapshot!("- - 0 Escape".parse::<Input>().unwrap_err(), @"invalid file: `Escape` is not a key");
+        assert_snapshot!("- - Key1".parse::<Input>().unwrap_err(), @"invalid file: `- - Key1` is not a recorded frame");
+    }
+}
@@ -0,0 +1,148 @@
+use clap::ValueEnum;
+
+use crate::brush::{Brush, Tool};
+use crate::emitter::Emitter;
+use crate::{Material, World};
+
+/// The demo levels bundled with the game, walls and emitters already in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Level {
+    /// Sand pours through the neck of an hourglass.
+    Hourglass,
+    /// Sand, water and oil go down three funnels onto a slope.
+    Funnels,
+    /// Sand rolls down shelves from one side to the other.
+    Ramps,
+}
+
+impl Level {
+    /// Draws the walls and puts the emitters of the level, scaled to the size of the world.
+    pub fn build(self, world: &mut World) {
+        let (width, height) = (world.width() as f32, world.height() as f32);
+        // Positions are given as a share of the size of the world
+        let at = |x: f32, y: f32| ((x * width) as isize, (y * height) as isize);
+        // Grains go diagonally, they would slip through the corners of thinner walls
+        let thickness = (world.width() / 160).max(2);
+        let wall = |world: &mut World, tool: Tool, from: (isize, isize), to: (isize, isize)| {
+            let brush = Brush {
+                material: Material::Wall,
+                size: thickness,
+                tool,
+                ..Brush::default()
+            };
+            world.draw(&brush, from, to, false);
+        };
+        let spout = (world.width() / 16).max(1);
+        let emitter = |x: f32, y: f32, material: Material| {
+            let (x, y) = at(x, y);
+            Emitter {
+                width: spout,
+                ..Emitter::new(x as usize, y as usize, material)
+            }
+        };
+
+        match self {
+            Level::Hourglass => {
+                // A few cells between the walls, whatever the size of the world
+                let gap = (world.width() / 80).max(2) as isize + thickness as isize;
+                let (middle, neck_y) = at(0.5, 0.5);
+                for (outside, neck) in [(0.15, middle - gap / 2), (0.85, middle + gap - gap / 2)] {
+                    wall(world, Tool::Line, at(outside, 0.1), (neck, neck_y));
+                    wall(world, Tool::Line, (neck, neck_y), at(outside, 0.9));
+                }
+                world.add_emitter(emitter(0.5, 0.05, Material::Sand));
+            }
+            Level::Funnels => {
+                for (centre, material) in [
+                    (0.2, Material::Sand),
+                    (0.5, Material::Water),
+                    (0.8, Material::Oil),
+                ] {
+                    wall(
+                        world,
+                        Tool::Line,
+                        at(centre - 0.12, 0.1),
+                        at(centre - 0.02, 0.4),
+                    );
+                    wall(
+                        world,
+                        Tool::Line,
+                        at(centre + 0.12, 0.1),
+                        at(centre + 0.02, 0.4),
+                    );
+                    world.add_emitter(emitter(centre, 0.05, material));
+                }
+                wall(world, Tool::Ramp, at(0.0, 0.7), at(0.4, 1.0));
+            }
+            Level::Ramps => {
+                wall(world, Tool::Line, at(0.1, 0.25), at(0.65, 0.4));
+                wall(world, Tool::Line, at(0.9, 0.5), at(0.35, 0.65));
+                wall(world, Tool::Rectangle, at(0.45, 0.85), at(0.55, 1.0));
+                world.add_emitter(emitter(0.25, 0.05, Material::Sand));
+            }
+        }
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+    use window_rs::WindowBuffer;
+
+    #[test]
+    fn test_hourglass() {
+        let mut world = World::new(40, 20, 0);
+        Level::Hourglass.build(&mut world);
+        let mut buffer = WindowBuffer::new(40, 20);
+        world.display(&mut buffer);
+        assert_snapshot!(buffer, @r###"
+        ........................................
+        .....##............##............##.....
+        .....####......................####.....
+        ......####....................####......
+        ........####................####........
+        .........####..............####.........
+        ...........####..........####...........
+        ............####........####............
+        ..............####....####..............
+        ...............####..####...............
+        ...............####..####...............
+        ..............####....####..............
+        ............####........####............
+        ...........####..........####...........
+        .........####..............####.........
+        ........####................####........
+        ......####....................####......
+        .....####......................####.....
+        .....##..........................##.....
+        ........................................
+        "###);
+
+        // The sand goes through the neck and piles up at the bottom
+        for _ in 0..200 {
+            world.update();
+        }
+        let below_the_neck = world
+            .grains()
+            .iter()
+            .filter(|sand| sand.material == Material::Sand && sand.y > 10)
+            .count();
+        assert!(below_the_neck > 20, "{below_the_neck} grains went through");
+    }
+
+    #[test]
+    fn test_every_level_fits() {
+        for level in Level::value_variants() {
+            let mut world = World::new(640, 360, 0);
+            level.build(&mut world);
+            assert!(world.grain_count() > 0);
+            assert!(!world.emitters().is_empty());
+            for emitter in world.emitters() {
+                assert!(emitter.spout().all(|(x, y)| x < 640 && y < 360));
+            }
+        }
+    }
+}
@@ -0,0 +1,2179 @@
+use clap::builder::RangedU64ValueParser;
+use clap::Parser;
+use graphic::Key;
+use pixel_sims::colour::{color_generator, rgb, shade};
+use pixel_sims::main_loop::MAX_TICKS_PER_FRAME;
+use pixel_sims::Simulation;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use window_rs::WindowBuffer;
+
+pub mod bench;
+pub mod body;
+pub mod brush;
+pub mod chunk;
+pub mod emitter;
+pub mod error;
+pub mod headless;
+pub mod input;
+pub mod level;
+mod light;
+pub mod material;
+mod physics;
+pub mod reaction;
+pub mod replay;
+pub mod save;
+pub mod window;
+
+pub use body::Body;
+pub use brush::{Brush, Direction, Shape, Tool};
+use chunk::Chunks;
+pub use emitter::Emitter;
+pub use error::Error;
+pub use headless::Headless;
+pub use input::Input;
+pub use level::Level;
+pub use material::Material;
+use physics::{SharedCells, Updater};
+pub use reaction::ReactionTable;
+pub use replay::Recorder;
+pub use window::GameWindow;
+
+// Speeds are in cells per tick. A grain at rest falls one cell per tick, and faster and
+// faster as long as nothing stops it.
+const REST_VELOCITY: f32 = 1.0;
+const GRAVITY: f32 = 0.25;
+const TERMINAL_VELOCITY: f32 = 5.0;
+// Grains landing at least that fast splash, and go sideways with part of their speed
+const SPLASH_VELOCITY: f32 = 3.0;
+const SPLASH_SPREAD: f32 = 0.5;
+// How much of its sideways speed a grain keeps from one tick to the next
+const FRICTION: f32 = 0.7;
+// Sideways speed given by a conveyor belt, and upwards by a fan
+const CONVEYOR_SPEED: f32 = 2.0;
+const FAN_SPEED: f32 = 3.0;
+// How much the time can be slowed down. It is sped up until the main loop runs its most
+// ticks per frame, past that it stops catching up anyway.
+const MIN_SPEED: f64 = 1.0 / 16.0;
+
+//CLI
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Cli {
+    /// Width of the world, in cells
+    #[arg(long, default_value_t = 640, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub width: usize,
+
+    /// Height of the world, in cells
+    #[arg(long, default_value_t = 360, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub height: usize,
+
+    /// Size of a cell on the screen, in pixels
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    pub scale: u8,
+
+    /// Frames drawn per second, at most
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..=240))]
+    pub fps: u32,
+
+    /// Updates of the world on each frame, at normal speed
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=MAX_TICKS_PER_FRAME as i64))]
+    pub ticks_per_frame: u32,
+
+    /// Start paused, the world only moves once it's unpaused or stepped
+    #[arg(long)]
+    pub paused: bool,
+
+    /// Seed of the random choices, the same seed and the same drawing give the same world
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// World to start from, a save file or a PNG level. Also where the save key writes
+    #[arg(long)]
+    pub file_path: Option<String>,
+
+    /// Reaction table to use instead of the bundled one, a TOML file like reactions.toml
+    #[arg(long)]
+    pub reactions: Option<String>,
+
+    /// Demo level to start from, drawn before --file-path is loaded
+    #[arg(long, value_enum)]
+    pub level: Option<Level>,
+
+    /// Pour grains from x,y[,material[,rate[,width]]], for example 320,10,water,0.2
+    #[arg(long)]
+    pub emitter: Vec<String>,
+
+    /// Paint the sand with the colours of the rainbow instead of the colours of sand
+    #[arg(long)]
+    pub rainbow: bool,
+
+    /// Record the mouse and the keys of every frame in this file
+    #[arg(long)]
+    pub record: Option<String>,
+
+    /// Play a recording again without a window, saving the window as PNG snapshots
+    #[arg(long)]
+    pub replay: Option<String>,
+
+    /// With --replay, also take a snapshot every N ticks, not only at the end
+    #[arg(long)]
+    pub snapshot_every: Option<u64>,
+
+    /// Folder of the --replay snapshots
+    #[arg(long, default_value = ".")]
+    pub snapshot_dir: String,
+
+    /// Run N frames without a window, as fast as possible, and print how long they took
+    #[arg(long, conflicts_with = "replay")]
+    pub bench_frames: Option<usize>,
+
+    /// With --bench-frames, add sand spread this way to the world before it starts
+    #[arg(long, value_enum, requires = "bench_frames")]
+    pub bench_layout: Option<bench::Layout>,
+
+    /// How many grains --bench-layout adds
+    #[arg(long, default_value_t = 50_000)]
+    pub bench_grains: usize,
+}
+//CLI END
+
+//KEYS
+// Number keys choose the material, and this one goes through every material of the
+// reaction table, the ones it adds too
+const NEXT_MATERIAL_KEY: Key = Key::M;
+const SMALLER_BRUSH_KEY: Key = Key::LeftBracket;
+const BIGGER_BRUSH_KEY: Key = Key::RightBracket;
+const SHAPE_KEY: Key = Key::C;
+const SPRAY_KEY: Key = Key::P;
+// Free drawing, lines, rectangles and ramps
+const TOOL_KEY: Key = Key::T;
+// Direction of the conveyors: right, left, or up for a fan
+const ROTATE_KEY: Key = Key::R;
+// Puts an emitter of the brush material under the mouse, or removes the one there
+const EMITTER_KEY: Key = Key::E;
+// Rainbow sand, or sand coloured sand
+const RAINBOW_KEY: Key = Key::B;
+// Drops a box under the mouse, or a ball with the round brush, as wide as the brush. It is
+// made of the brush material when it's a solid one, of wood otherwise.
+const BODY_KEY: Key = Key::O;
+// Empties the world: grains, bodies and emitters
+const CLEAR_KEY: Key = Key::Delete;
+// Stops the time, and moves it one tick while it's stopped
+const PAUSE_KEY: Key = Key::Space;
+const STEP_KEY: Key = Key::N;
+// Twice as fast, or twice as slow
+const FASTER_KEY: Key = Key::Equal;
+const SLOWER_KEY: Key = Key::Minus;
+// Save to `--file-path`, or reload it
+const SAVE_KEY: Key = Key::Save;
+const LOAD_KEY: Key = Key::L;
+// The right mouse button erases
+//KEYS END
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sand {
+    pub x: usize,
+    pub y: usize,
+
+    /// `None` takes the colour the material is known by in the reaction table.
+    pub color: Option<u32>,
+    pub material: Material,
+}
+
+impl Sand {
+    /// A grain with the colour of its material.
+    pub fn new(x: usize, y: usize, material: Material) -> Self {
+        Self {
+            x,
+            y,
+            color: None,
+            material,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    material: Material,
+    color: u32,
+    // Ticks left before fire and smoke go away
+    life: u8,
+    // Same as the world tick once the particle has been updated this tick, so that
+    // it isn't moved twice
+    tick: u32,
+    // (sideways, downwards). Conveyors don't move, it's the push they give.
+    velocity: (f32, f32),
+    // How far the grain is between two cells, it moves once it gets to a whole cell
+    offset: (f32, f32),
+    temperature: f32,
+}
+
+impl Cell {
+    // As hot, and as long lived, as the reaction table makes its material
+    fn new(material: Material, color: u32, tick: u32, reactions: &ReactionTable) -> Self {
+        Self {
+            material,
+            color,
+            life: reactions[material].life,
+            tick,
+            velocity: match material {
+                Material::Conveyor => Direction::Right.push(),
+                _ => (0.0, REST_VELOCITY),
+            },
+            offset: (0.0, 0.0),
+            temperature: reactions.temperature(material),
+        }
+    }
+}
+
+// The grains live in a dense grid, one cell per pixel, so that knowing if a cell is
+// taken doesn't need to look at every other grain. The grid is split in chunks that are
+// only updated and drawn when something changed in them.
+pub struct World {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<Cell>>,
+    chunks: Chunks,
+    tick: u32,
+    // Saved with the world, a loaded world starts again from it
+    seed: u64,
+    rng: StdRng,
+    colors: Box<dyn Iterator<Item = (u8, u8, u8)>>,
+    // New sand takes the colours of the rainbow one after the other
+    rainbow: bool,
+    brush: Brush,
+    emitters: Vec<Emitter>,
+    // Not saved, their cells are saved as the material they're made of
+    bodies: Vec<Body>,
+    reactions: ReactionTable,
+    // Where the mouse was on the last frame, while a button is held down
+    last_mouse: Option<(isize, isize)>,
+    // Where the button was pressed, and if it erases, while a shape is dragged
+    drag: Option<((isize, isize), bool)>,
+}
+
+impl World {
+    pub fn new(width: usize, height: usize, seed: u64) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width * height],
+            chunks: Chunks::new(width, height),
+            tick: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            colors: Box::new(color_generator()),
+            rainbow: false,
+            brush: Brush::default(),
+            emitters: Vec::new(),
+            bodies: Vec::new(),
+            reactions: ReactionTable::default(),
+            last_mouse: None,
+            drag: None,
+        }
+    }
+
+    pub fn with_grains(width: usize, height: usize, seed: u64, grains: Vec<Sand>) -> Self {
+        let mut world = World::new(width, height, seed);
+        for sand in grains {
+            world.add(sand);
+        }
+        world
+    }
+
+    /// Builds the world asked for on the command line: the reaction table, the demo level,
+    /// then the level file and the emitters if there are some.
+    pub fn from_cli(cli: &Cli) -> Result<Self, Error> {
+        let mut world = World::new(cli.width, cli.height, cli.seed);
+        world.rainbow = cli.rainbow;
+        if let Some(reactions) = &cli.reactions {
+            world.reactions = ReactionTable::load_from_path(Path::new(reactions))?;
+        }
+        if let Some(level) = cli.level {
+            level.build(&mut world);
+        }
+        if let Some(file_path) = &cli.file_path {
+            world.load(Path::new(file_path))?;
+        }
+        for emitter in &cli.emitter {
+            world.add_emitter(Emitter::parse(emitter, &world.reactions)?);
+        }
+        Ok(world)
+    }
+
+    /// Replaces the grains with the ones of a save file or a PNG level of the same size,
+    /// the window can't be resized. Its materials are the ones of the reaction table. The
+    /// brush, the emitters, the reactions and the rainbow mode are kept.
+    pub fn load(&mut self, path: &Path) -> Result<(), Error> {
+        let loaded =
+            save::load_from_path(path, (self.width, self.height), self.seed, &self.reactions)?;
+
+        let brush = self.brush.clone();
+        let emitters = std::mem::take(&mut self.emitters);
+        *self = World {
+            brush,
+            emitters,
+            rainbow: self.rainbow,
+            ..loaded
+        };
+        Ok(())
+    }
+
+    /// Takes every grain, body and emitter away. The brush and the reactions are kept.
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+        self.bodies.clear();
+        self.emitters.clear();
+        self.chunks = Chunks::new(self.width, self.height);
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rainbow(&self) -> bool {
+        self.rainbow
+    }
+
+    /// Only the sand painted from now on changes colour.
+    pub fn set_rainbow(&mut self, rainbow: bool) {
+        self.rainbow = rainbow;
+    }
+
+    /// What the mouse paints with.
+    pub fn brush(&self) -> &Brush {
+        &self.brush
+    }
+
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    /// Removes the emitter whose spout is on (x, y), or puts one there that pours the
+    /// brush material through a spout as wide as the brush.
+    pub fn toggle_emitter(&mut self, x: usize, y: usize) {
+        let under = self
+            .emitters
+            .iter()
+            .position(|emitter| emitter.spout().any(|cell| cell == (x, y)));
+        match under {
+            Some(index) => {
+                let emitter = self.emitters.remove(index);
+                // The spout is drawn over the cells, they have to be drawn again
+                for (x, y) in emitter.spout() {
+                    if x < self.width && y < self.height {
+                        self.chunks.touch(x, y);
+                    }
+                }
+            }
+            None => self.emitters.push(Emitter {
+                width: self.brush.size,
+                ..Emitter::new(x, y, self.brush.material)
+            }),
+        }
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    /// Drops a body in the world, the particles in the way are pushed up above it. Nothing
+    /// happens, and it returns false, when a wall or another body is in the way, or when the
+    /// particles have no room above it.
+    pub fn add_body(&mut self, body: Body) -> bool {
+        if !self.has_room(&body) {
+            return false;
+        }
+        let color = self.reactions[body.material].random_color(&mut self.rng);
+        let footprint: HashSet<_> = body.cells().into_iter().collect();
+        let cells = body
+            .cells()
+            .into_iter()
+            .map(|(x, y)| {
+                // The edges are darker, so that the body stands out of the ground
+                let edge = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .any(|cell| !footprint.contains(cell));
+                let color = if edge { shade(color, 0.7) } else { color };
+                Some(Cell::new(body.material, color, self.tick, &self.reactions))
+            })
+            .collect();
+        self.stamp(&body, cells);
+        self.bodies.push(body);
+        true
+    }
+
+    /// How the materials hold their heat, and react.
+    pub fn reactions(&self) -> &ReactionTable {
+        &self.reactions
+    }
+
+    /// The particles already in the world keep their temperature. The table has to have
+    /// their materials, and the ones of the brush and of the emitters.
+    pub fn set_reactions(&mut self, reactions: ReactionTable) {
+        self.reactions = reactions;
+    }
+
+    /// The temperature of the grain at (x, y), if there is one.
+    pub fn temperature(&self, x: usize, y: usize) -> Option<f32> {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x].map(|cell| cell.temperature)
+        } else {
+            None
+        }
+    }
+
+    /// The material of the grain at (x, y), if there is one.
+    pub fn get(&self, x: usize, y: usize) -> Option<Material> {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x].map(|cell| cell.material)
+        } else {
+            None
+        }
+    }
+
+    /// Puts a grain in the world, replacing the one already there, as hot as the reaction
+    /// table makes its material. Grains outside of the world are dropped.
+    pub fn add(&mut self, sand: Sand) {
+        if sand.x < self.width && sand.y < self.height {
+            let color = sand
+                .color
+                .unwrap_or_else(|| self.reactions[sand.material].color());
+            self.cells[sand.y * self.width + sand.x] =
+                Some(Cell::new(sand.material, color, self.tick, &self.reactions));
+            self.chunks.touch(sand.x, sand.y);
+        }
+    }
+
+    /// Every grain of the world, line by line from the top left.
+    pub fn grains(&self) -> Vec<Sand> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cell)| {
+                cell.map(|cell| Sand {
+                    x: index % self.width,
+                    y: index / self.width,
+                    color: Some(cell.color),
+                    material: cell.material,
+                })
+            })
+            .collect()
+    }
+
+    pub fn grain_count(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    /// How many particles of each material that moves are in the world, the ones with none
+    /// are left out.
+    pub fn counts(&self) -> Vec<(Material, usize)> {
+        let mut counts = vec![0; self.reactions.materials().count()];
+        for cell in self.cells.iter().flatten() {
+            counts[cell.material.index()] += 1;
+        }
+        self.reactions
+            .materials()
+            .zip(counts)
+            .filter(|(material, count)| !self.reactions[*material].is_static() && *count > 0)
+            .collect()
+    }
+
+    pub fn update(&mut self) {
+        self.emit();
+        self.tick = self.tick.wrapping_add(1);
+        let seed: u64 = self.rng.gen();
+        let cells = SharedCells::new(&mut self.cells);
+        let chunks = &self.chunks;
+        let reactions = &self.reactions;
+        let (width, height, tick) = (self.width, self.height, self.tick);
+
+        for pass in chunk::PASSES {
+            chunks.in_pass(pass).into_par_iter().for_each(|chunk| {
+                let mut updater = Updater {
+                    cells,
+                    chunks,
+                    width,
+                    height,
+                    tick,
+                    rng: StdRng::seed_from_u64(seed ^ chunks.index(chunk) as u64),
+                    reactions,
+                };
+                updater.update_chunk(chunk);
+            });
+        }
+        self.update_bodies();
+
+        self.chunks.next_tick();
+    }
+
+    // The bodies fall after the particles moved, into the cells that are free or taken by a
+    // fluid. The ones with no cell left, burnt or erased, go away.
+    fn update_bodies(&mut self) {
+        let mut bodies = std::mem::take(&mut self.bodies);
+        bodies.retain_mut(|body| {
+            body.offset += body.velocity;
+            body.velocity = (body.velocity + GRAVITY).min(TERMINAL_VELOCITY);
+            while body.offset >= 1.0 {
+                body.offset -= 1.0;
+                if !self.can_fall(body) {
+                    body.velocity = REST_VELOCITY;
+                    body.offset = 0.0;
+                    break;
+                }
+                let cells = self.lift(body);
+                body.y += 1;
+                self.stamp(body, cells);
+            }
+            body.cells().into_iter().any(|(x, y)| {
+                self.cell_at(x, y)
+                    .and_then(|index| self.cells[index])
+                    .is_some_and(|cell| cell.material == body.material)
+            })
+        });
+        self.bodies = bodies;
+    }
+
+    // No static cell in the way of the body, and a free cell over it for every particle it
+    // pushes up
+    fn has_room(&self, body: &Body) -> bool {
+        let mut pushed = BTreeMap::new();
+        for (x, y) in body.cells() {
+            let Some(cell) = self.cell_at(x, y).and_then(|index| self.cells[index]) else {
+                continue;
+            };
+            if self.reactions[cell.material].is_static() {
+                return false;
+            }
+            *pushed.entry(x).or_insert(0) += 1;
+        }
+        body.tops()
+            .into_iter()
+            .filter_map(|(x, top)| Some((x, top, *pushed.get(&x)?)))
+            .all(|(x, top, particles)| {
+                let free = (0..top)
+                    .filter(|y| {
+                        self.cell_at(x, *y)
+                            .is_some_and(|index| self.cells[index].is_none())
+                    })
+                    .count();
+                free >= particles
+            })
+    }
+
+    // Into the free cells, and the fluids lighter than the body: they go up to the cell it
+    // leaves on top of their column
+    fn can_fall(&self, body: &Body) -> bool {
+        let below = body.cells_below().into_iter();
+        below.zip(body.tops()).all(|((x, y), (_, top))| {
+            // Above the world, the body slides in from the top
+            if y < 0 {
+                return true;
+            }
+            let Some(index) = self.cell_at(x, y) else {
+                return false;
+            };
+            let Some(cell) = self.cells[index] else {
+                return true;
+            };
+            let left = self.cell_at(x, top).map(|index| self.cells[index]);
+            let reactions = &self.reactions;
+            reactions[cell.material].is_fluid()
+                && reactions[cell.material].density < reactions[body.material].density
+                && left.is_some_and(|left| left.is_none_or(|left| left.material == body.material))
+        })
+    }
+
+    // Takes the cells of a body out of the world, in the order of `Body::cells`. The ones
+    // that burnt, were erased, or are outside of the world are `None`.
+    fn lift(&mut self, body: &Body) -> Vec<Option<Cell>> {
+        body.cells()
+            .into_iter()
+            .map(|(x, y)| {
+                let index = self.cell_at(x, y)?;
+                let cell = self.cells[index].filter(|cell| cell.material == body.material)?;
+                self.cells[index] = None;
+                self.chunks.touch(x as usize, y as usize);
+                Some(cell)
+            })
+            .collect()
+    }
+
+    // Puts the cells of a body in the world, in the order of `Body::cells`. The particles in
+    // the way go up to the first free cell above the body, `has_room` and `can_fall` make
+    // sure there is one. Nothing is ever lost: a cell of the body that would have to replace
+    // a static cell, or a particle with nowhere to go, is dropped instead.
+    fn stamp(&mut self, body: &Body, cells: Vec<Option<Cell>>) {
+        let footprint = body.cells();
+        let inside: HashSet<_> = footprint.iter().copied().collect();
+        for ((x, y), cell) in footprint.into_iter().zip(cells) {
+            let (Some(index), Some(cell)) = (self.cell_at(x, y), cell) else {
+                continue;
+            };
+            if let Some(particle) = self.cells[index] {
+                if self.reactions[particle.material].is_static() {
+                    continue;
+                }
+                let free = (0..y).rev().find_map(|above| {
+                    let free = self.cell_at(x, above)?;
+                    (self.cells[free].is_none() && !inside.contains(&(x, above))).then_some(free)
+                });
+                let Some(free) = free else {
+                    continue;
+                };
+                self.cells[free] = Some(particle);
+                self.chunks.touch(x as usize, free / self.width);
+            }
+            self.cells[index] = Some(cell);
+            self.chunks.touch(x as usize, y as usize);
+        }
+    }
+
+    // Each emitter lets grains out of the free cells of its spout
+    fn emit(&mut self) {
+        for index in 0..self.emitters.len() {
+            let emitter = self.emitters[index].clone();
+            for (x, y) in emitter.spout() {
+                let free = self
+                    .cell_at(x as isize, y as isize)
+                    .is_some_and(|index| self.cells[index].is_none());
+                if free && self.rng.gen_bool(emitter.rate) {
+                    let color = self.color(emitter.material);
+                    self.add(Sand {
+                        x,
+                        y,
+                        color: Some(color),
+                        material: emitter.material,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Number of chunks updated on the next tick, the others are asleep.
+    pub fn awake_chunks(&self) -> usize {
+        self.chunks.awake_count()
+    }
+
+    /// Draws the chunks that changed since the last call, lit and shaded, the buffer has to
+    /// be the same every time.
+    pub fn display(&self, buffer: &mut WindowBuffer) {
+        for chunk in self.chunks.take_dirty() {
+            let (columns, rows) = self.chunks.bounds(chunk);
+            // What changed in the chunk casts its shadow and its light a few cells out of it,
+            // they are drawn again too
+            let columns = columns.start.saturating_sub(light::GLOW_RADIUS)
+                ..(columns.end + light::GLOW_RADIUS).min(self.width);
+            let rows = rows.start.saturating_sub(light::GLOW_RADIUS)
+                ..(rows.end + light::DEPTH_REACH.max(light::GLOW_RADIUS)).min(self.height);
+            light::draw(self, columns, rows, buffer);
+        }
+
+        // The spouts are drawn darker, over whatever comes out of them
+        for emitter in &self.emitters {
+            for (x, y) in emitter.spout() {
+                if x < self.width && y < self.height {
+                    buffer[(x, y)] = shade(self.reactions[emitter.material].color(), 0.5);
+                }
+            }
+        }
+    }
+
+    pub fn handle_user_input(&mut self, window: &impl GameWindow) {
+        self.handle_input(&Input::from_window(window));
+    }
+
+    pub fn handle_input(&mut self, input: &Input) {
+        for key in &input.keys {
+            match *key {
+                SMALLER_BRUSH_KEY => self.brush.shrink(),
+                BIGGER_BRUSH_KEY => self.brush.grow(),
+                SHAPE_KEY => self.brush.toggle_shape(),
+                SPRAY_KEY => self.brush.spray = !self.brush.spray,
+                RAINBOW_KEY => self.rainbow = !self.rainbow,
+                CLEAR_KEY => self.clear(),
+                BODY_KEY => {
+                    if let Some((x, y)) =
+                        input.mouse.filter(|(x, y)| self.cell_at(*x, *y).is_some())
+                    {
+                        let material = if self.reactions[self.brush.material].is_static() {
+                            self.brush.material
+                        } else {
+                            Material::Wood
+                        };
+                        let body = Body::new(x, y, self.brush.shape, self.brush.size, material);
+                        self.add_body(body);
+                    }
+                }
+                NEXT_MATERIAL_KEY => {
+                    self.brush.material = self.reactions.next(self.brush.material);
+                }
+                TOOL_KEY => self.brush.next_tool(),
+                ROTATE_KEY => self.brush.rotate(),
+                EMITTER_KEY => {
+                    if let Some(index) = input.mouse.and_then(|(x, y)| self.cell_at(x, y)) {
+                        self.toggle_emitter(index % self.width, index / self.width);
+                    }
+                }
+                key => {
+                    if let Some(material) = Material::from_key(key) {
+                        self.brush.material = material;
+                    }
+                }
+            }
+        }
+
+        if input.scroll > 0.0 {
+            self.brush.grow();
+        } else if input.scroll < 0.0 {
+            self.brush.shrink();
+        }
+
+        match input.mouse {
+            Some(position) if input.left || input.right => {
+                if self.brush.tool == Tool::Free {
+                    let from = self.last_mouse.unwrap_or(position);
+                    self.stroke(from, position, input.right);
+                } else {
+                    self.drag.get_or_insert((position, input.right));
+                }
+                self.last_mouse = Some(position);
+            }
+            _ => {
+                // The shape is drawn once the button is released, where the mouse was last
+                if let (Some((start, erase)), Some(end)) = (self.drag.take(), self.last_mouse) {
+                    let brush = self.brush.clone();
+                    self.draw(&brush, start, end, erase);
+                }
+                self.last_mouse = None;
+            }
+        }
+    }
+
+    fn cell_at(&self, x: isize, y: isize) -> Option<usize> {
+        let in_world = x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height;
+        in_world.then(|| y as usize * self.width + x as usize)
+    }
+
+    /// Paints, or erases, with the brush all along the segment from `from` to `to`.
+    pub fn stroke(&mut self, from: (isize, isize), to: (isize, isize), erase: bool) {
+        for (x, y) in brush::line(from, to) {
+            if erase {
+                self.erase(x, y);
+            } else {
+                self.paint(x, y);
+            }
+        }
+    }
+
+    /// Paints, or erases, the shape of the tool of `brush` dragged from `from` to `to`.
+    pub fn draw(&mut self, brush: &Brush, from: (isize, isize), to: (isize, isize), erase: bool) {
+        for (x, y) in brush.shape(from, to) {
+            if erase {
+                self.remove(x, y);
+            } else {
+                self.put(brush, x, y);
+            }
+        }
+    }
+
+    /// Paints with the brush around (x, y), the part of the brush outside of the world is
+    /// cut off.
+    pub fn paint(&mut self, x: isize, y: isize) {
+        let brush = self.brush.clone();
+        for (x, y) in brush.cells(x, y) {
+            self.put(&brush, x, y);
+        }
+    }
+
+    /// Removes every grain under the brush around (x, y).
+    pub fn erase(&mut self, x: isize, y: isize) {
+        for (x, y) in self.brush.cells(x, y) {
+            self.remove(x, y);
+        }
+    }
+
+    // One cell of the brush, outside of the world it is cut off
+    fn put(&mut self, brush: &Brush, x: isize, y: isize) {
+        if self.cell_at(x, y).is_none() {
+            return;
+        }
+        if brush.spray && !self.rng.gen_bool(brush::SPRAY_RATE) {
+            return;
+        }
+
+        let mut color = self.color(brush.material);
+        if brush.material == Material::Conveyor {
+            // Belts going left are darker and fans brighter, to tell them apart
+            color = match brush.direction {
+                Direction::Left => shade(color, 0.7),
+                Direction::Right => color,
+                Direction::Up => shade(color, 1.4),
+            };
+        }
+        let sand = Sand {
+            x: x as usize,
+            y: y as usize,
+            color: Some(color),
+            material: brush.material,
+        };
+        self.add(sand);
+
+        if let Some(Some(cell)) = self.cells.get_mut(y as usize * self.width + x as usize) {
+            if cell.material == Material::Conveyor {
+                cell.velocity = brush.direction.push();
+            }
+        }
+    }
+
+    fn remove(&mut self, x: isize, y: isize) {
+        if let Some(index) = self.cell_at(x, y) {
+            self.cells[index] = None;
+            self.chunks.touch(x as usize, y as usize);
+        }
+    }
+
+    // A shade of the palette of the material, or the next colour of the rainbow for sand in
+    // rainbow mode
+    fn color(&mut self, material: Material) -> u32 {
+        match material {
+            Material::Sand if self.rainbow => {
+                let (r, g, b) = self.colors.next().unwrap();
+                rgb(r, g, b)
+            }
+            material => self.reactions[material].random_color(&mut self.rng),
+        }
+    }
+}
+
+pub struct Game {
+    pub world: World,
+    pub cli: Cli,
+    pub recorder: Option<Recorder>,
+    pub paused: bool,
+    /// How many times faster than `--ticks-per-frame` the world goes.
+    pub speed: f64,
+}
+
+impl Game {
+    /// Paused when the command line asks for it.
+    pub fn new(world: World, cli: Cli, recorder: Option<Recorder>) -> Self {
+        Self {
+            world,
+            paused: cli.paused,
+            cli,
+            recorder,
+            speed: 1.0,
+        }
+    }
+
+    /// Time between two frames, from `--fps`.
+    pub fn frame(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.cli.fps as f64)
+    }
+
+    // Runs one tick, and counts it in the recording
+    fn step(&mut self) {
+        self.world.update();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.tick();
+        }
+    }
+
+    // The world that was loaded isn't in the recording, what comes after it couldn't be
+    // replayed
+    fn stop_recording(&mut self) -> Result<(), Error> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(());
+        };
+        eprintln!("recording stopped: a world was loaded");
+        recorder.finish()
+    }
+
+    pub fn save_path(&self) -> PathBuf {
+        PathBuf::from(self.cli.file_path.as_deref().unwrap_or("world.sand"))
+    }
+
+    /// The name of the window, with the number of particles of each material and the speed
+    /// of the time.
+    pub fn title(&self) -> String {
+        let counts: Vec<String> = self
+            .world
+            .counts()
+            .iter()
+            .map(|(material, count)| format!("{count} {}", self.world.reactions()[*material].name))
+            .collect();
+        let mut parts = vec!["Grains de sable".to_string()];
+        if !counts.is_empty() {
+            parts.push(counts.join(", "));
+        }
+        if self.paused {
+            parts.push("paused".to_string());
+        } else if self.speed != 1.0 {
+            parts.push(format!("x{}", self.speed));
+        }
+        parts.push("ESC to exit".to_string());
+        parts.join(" - ")
+    }
+}
+
+impl<G: GameWindow> Simulation<G> for Game {
+    fn update(&mut self) {
+        if !self.paused {
+            self.step();
+        }
+    }
+
+    fn render(&self, buffer: &mut WindowBuffer) {
+        self.world.display(buffer);
+    }
+
+    // Faster, the ticks are shorter and more of them run on each frame
+    fn tick(&self) -> Duration {
+        self.frame()
+            .div_f64(self.cli.ticks_per_frame as f64 * self.speed)
+    }
+
+    fn handle_input(&mut self, window: &G) {
+        // The time isn't recorded, only the ticks that it ran
+        if window.is_key_pressed(PAUSE_KEY) {
+            self.paused = !self.paused;
+        }
+        if window.is_key_pressed(STEP_KEY) && self.paused {
+            self.step();
+        }
+        if window.is_key_pressed(FASTER_KEY)
+            && self.cli.ticks_per_frame as f64 * self.speed * 2.0 <= MAX_TICKS_PER_FRAME as f64
+        {
+            self.speed *= 2.0;
+        }
+        if window.is_key_pressed(SLOWER_KEY) {
+            self.speed = (self.speed / 2.0).max(MIN_SPEED);
+        }
+
+        let mut input = Input::from_window(window);
+        // The window is `--scale` times bigger than the world
+        let scale = self.cli.scale as isize;
+        input.mouse = input
+            .mouse
+            .map(|(x, y)| (x.div_euclid(scale), y.div_euclid(scale)));
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.record(&input) {
+                eprintln!("recording stopped: {error}");
+                self.recorder = None;
+            }
+        }
+        self.world.handle_input(&input);
+
+        let path = self.save_path();
+        let result = if window.is_key_pressed(SAVE_KEY) {
+            save::save_to_path(&self.world, &path)
+        } else if window.is_key_pressed(LOAD_KEY) {
+            self.world.load(&path).and_then(|()| self.stop_recording())
+        } else {
+            Ok(())
+        };
+        // The window has no room for messages, a failed save mustn't stop the game
+        if let Err(error) = result {
+            eprintln!("{}: {error}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+    use pixel_sims::colour::channels;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::collections::{HashMap, HashSet};
+
+    const WIDTH: usize = 640;
+
+    #[test]
+    fn simple_sand_drop() {
+        let mut buffer = WindowBuffer::new(5, 4);
+        let mut world = World::with_grains(5, 4, 0, vec![Sand::new(3, 0, Material::Sand)]);
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        ...#.
+        .....
+        .....
+        .....
+        "###
+        );
+
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        ...#.
+        .....
+        .....
+        "###
+        );
+
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        .....
+        ...#.
+        .....
+        "###
+        );
+    }
+
+    #[test]
+    fn test_y_bigger_than_buffer() {
+        let mut buffer = WindowBuffer::new(5, 4);
+        let mut world = World::with_grains(
+            5,
+            4,
+            0,
+            vec![
+                Sand::new(WIDTH / 2, 3, Material::Sand),
+                Sand::new(2, 4, Material::Sand),
+                Sand::new(2, 3, Material::Sand),
+            ],
+        );
+
+        // The grains outside of the world are dropped, the one on the bottom line stays there
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        .....
+        .....
+        ..#..
+        "###
+        );
+    }
+
+    #[test]
+    fn edges_are_walls() {
+        let mut world = World::with_grains(
+            3,
+            3,
+            0,
+            vec![
+                Sand::new(0, 0, Material::Sand),
+                Sand::new(0, 1, Material::Sand),
+                Sand::new(0, 2, Material::Sand),
+                Sand::new(2, 1, Material::Water),
+            ],
+        );
+        for _ in 0..5 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ...
+        s..
+        ssw
+        "###);
+    }
+
+    #[test]
+    fn brush_is_clipped_at_the_borders() {
+        let mut world = World::new(5, 4, 0);
+        world.paint(0, 0);
+        world.paint(4, 3);
+        world.paint(100, 100);
+        assert_snapshot!(materials(&world), @r###"
+        ss...
+        sssss
+        ..sss
+        ..sss
+        "###);
+    }
+
+    #[test]
+    fn sand_physic() {
+        let mut buffer = WindowBuffer::new(5, 4);
+        let mut world = World::with_grains(
+            5,
+            4,
+            0,
+            vec![
+                Sand::new(2, 2, Material::Sand),
+                Sand::new(2, 1, Material::Sand),
+                Sand::new(2, 0, Material::Sand),
+            ],
+        );
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        ..#..
+        ..#..
+        ..#..
+        .....
+        "###
+        );
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        ..#..
+        ..#..
+        ..#..
+        "###
+        );
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        .....
+        ..#..
+        ..##.
+        "###
+        );
+        world.update();
+        world.display(&mut buffer);
+        assert_snapshot!(
+            buffer.to_string(),
+            @r###"
+        .....
+        .....
+        .....
+        .###.
+        "###
+        );
+    }
+
+    // One character per material, to tell them apart in the snapshots
+    fn materials(world: &World) -> String {
+        let mut map = String::new();
+        for y in 0..world.height() {
+            for x in 0..world.width() {
+                map.push(match world.get(x, y) {
+                    None => '.',
+                    Some(Material::Sand) => 's',
+                    Some(Material::Water) => 'w',
+                    Some(Material::Stone) => '#',
+                    Some(Material::Wood) => '=',
+                    Some(Material::Fire) => '^',
+                    Some(Material::Smoke) => '~',
+                    Some(Material::Oil) => 'o',
+                    Some(Material::Wall) => '@',
+                    Some(Material::Drain) => 'v',
+                    Some(Material::Conveyor) => '>',
+                    Some(Material::Steam) => ':',
+                    Some(Material::Lava) => '*',
+                    Some(Material::Glass) => '+',
+                    // Added by the reaction table
+                    Some(_) => 'm',
+                });
+            }
+            map.push('\n');
+        }
+        map
+    }
+
+    #[test]
+    fn water_flows_sideways() {
+        let mut world = World::with_grains(
+            5,
+            3,
+            0,
+            vec![
+                Sand::new(2, 2, Material::Water),
+                Sand::new(2, 1, Material::Water),
+                Sand::new(2, 0, Material::Water),
+            ],
+        );
+        for _ in 0..10 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        .....
+        .ww.w
+        "###);
+    }
+
+    #[test]
+    fn stone_holds_sand() {
+        let mut world = World::with_grains(
+            5,
+            4,
+            0,
+            vec![
+                Sand::new(1, 2, Material::Stone),
+                Sand::new(2, 2, Material::Stone),
+                Sand::new(3, 2, Material::Stone),
+                Sand::new(2, 0, Material::Sand),
+                Sand::new(2, 1, Material::Sand),
+            ],
+        );
+        for _ in 0..10 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        ..s..
+        .###.
+        ....s
+        "###);
+    }
+
+    #[test]
+    fn fire_burns_wood_into_smoke() {
+        let mut world = World::with_grains(
+            5,
+            4,
+            0,
+            vec![
+                Sand::new(0, 2, Material::Fire),
+                Sand::new(1, 3, Material::Wood),
+                Sand::new(0, 3, Material::Wood),
+                Sand::new(2, 3, Material::Wood),
+                Sand::new(4, 3, Material::Wood),
+            ],
+        );
+        world.update();
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        .....
+        ^....
+        ^==.=
+        "###);
+        world.update();
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        .....
+        ^....
+        ^^=.=
+        "###);
+        let life = |material: Material| world.reactions()[material].life;
+        let (fire, smoke) = (life(Material::Fire), life(Material::Smoke));
+        for _ in 0..fire {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ~....
+        .....
+        ~....
+        .~^.=
+        "###);
+
+        // The wood on its own doesn't catch fire, and the smoke is gone
+        for _ in 0..fire + smoke {
+            world.update();
+        }
+        let grains: Vec<_> = world
+            .grains()
+            .into_iter()
+            .map(|sand| (sand.x, sand.y, sand.material))
+            .collect();
+        assert_eq!(grains, vec![(4, 3, Material::Wood)]);
+    }
+
+    #[test]
+    fn sand_sinks_through_water_and_oil() {
+        let mut world = World::with_grains(
+            1,
+            4,
+            0,
+            vec![
+                Sand::new(0, 0, Material::Sand),
+                Sand::new(0, 1, Material::Water),
+                Sand::new(0, 2, Material::Oil),
+                Sand::new(0, 3, Material::Water),
+            ],
+        );
+        world.update();
+        assert_snapshot!(materials(&world), @r###"
+        o
+        s
+        w
+        w
+        "###);
+        for _ in 0..4 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        o
+        w
+        w
+        s
+        "###);
+    }
+
+    fn count(world: &World) -> HashMap<Material, usize> {
+        let mut count = HashMap::new();
+        for sand in world.grains() {
+            *count.entry(sand.material).or_insert(0) += 1;
+        }
+        count
+    }
+
+    // Fire and smoke don't last, they are kept out of the mass checks
+    fn lasting_material() -> impl Strategy<Value = Option<Material>> {
+        prop_oneof![
+            Just(None),
+            Just(Some(Material::Sand)),
+            Just(Some(Material::Water)),
+            Just(Some(Material::Oil)),
+            Just(Some(Material::Stone)),
+            Just(Some(Material::Wood)),
+        ]
+    }
+
+    fn falling_material() -> impl Strategy<Value = Option<Material>> {
+        prop_oneof![
+            Just(None),
+            Just(Some(Material::Sand)),
+            Just(Some(Material::Water)),
+            Just(Some(Material::Oil)),
+        ]
+    }
+
+    fn world_from_cells(width: usize, cells: &[Option<Material>]) -> World {
+        let grains = cells
+            .iter()
+            .enumerate()
+            .filter_map(|(index, material)| {
+                material.map(|material| Sand::new(index % width, index / width, material))
+            })
+            .collect();
+        World::with_grains(width, cells.len() / width, 0, grains)
+    }
+
+    proptest! {
+        #[test]
+        fn mass_is_conserved(
+            (width, cells) in (1usize..12, 1usize..12).prop_flat_map(|(width, height)| {
+                (Just(width), vec(lasting_material(), width * height))
+            }),
+            ticks in 1usize..30,
+        ) {
+            let mut world = world_from_cells(width, &cells);
+            let before = count(&world);
+            for _ in 0..ticks {
+                world.update();
+            }
+            prop_assert_eq!(count(&world), before);
+        }
+
+        #[test]
+        fn columns_settle_by_density(column in vec(falling_material(), 1..16)) {
+            let height = column.len();
+            let mut world = world_from_cells(1, &column);
+            for _ in 0..height * height {
+                world.update();
+            }
+
+            // From the bottom up: the grains, heaviest first, then nothing
+            let settled: Vec<Option<Material>> = (0..height).rev().map(|y| world.get(0, y)).collect();
+            let grains = column.iter().filter(|material| material.is_some()).count();
+            prop_assert!(settled[..grains].iter().all(|material| material.is_some()));
+            prop_assert!(settled[grains..].iter().all(|material| material.is_none()));
+            let densities: Vec<u8> = settled[..grains]
+                .iter()
+                .map(|material| world.reactions()[material.unwrap()].density)
+                .collect();
+            prop_assert!(densities.windows(2).all(|pair| pair[0] >= pair[1]));
+        }
+    }
+
+    // Pours one grain per tick in the middle of the top line, then lets the pile settle
+    fn pour(width: usize, height: usize, seed: u64, grains: usize) -> World {
+        let mut world = World::new(width, height, seed);
+        for _ in 0..grains {
+            world.add(Sand::new(width / 2, 0, Material::Sand));
+            world.update();
+        }
+        for _ in 0..width + height {
+            world.update();
+        }
+        world
+    }
+
+    #[test]
+    fn centred_pour_makes_a_symmetric_pile() {
+        assert_snapshot!(materials(&pour(11, 6, 0, 9)), @r###"
+        ...........
+        ...........
+        ...........
+        .....s.....
+        ....sss....
+        ...sssss...
+        "###);
+        assert_snapshot!(materials(&pour(11, 6, 1, 16)), @r###"
+        ...........
+        ...........
+        .....s.....
+        ....sss....
+        ...sssss...
+        ..sssssss..
+        "###);
+    }
+
+    #[test]
+    fn piles_dont_lean() {
+        // Over many seeds, as many grains end up on each side of the pour
+        let (mut left, mut right) = (0usize, 0usize);
+        for seed in 0..50 {
+            for sand in pour(21, 10, seed, 40).grains() {
+                if sand.x < 10 {
+                    left += 1;
+                } else if sand.x > 10 {
+                    right += 1;
+                }
+            }
+        }
+        let difference = left.abs_diff(right);
+        assert!(
+            difference * 20 < left + right,
+            "{left} grains on the left and {right} on the right"
+        );
+    }
+
+    #[test]
+    fn grains_fall_faster_up_to_terminal_velocity() {
+        let mut world = World::with_grains(1, 400, 0, vec![Sand::new(0, 0, Material::Sand)]);
+        let mut heights = vec![0];
+        for _ in 0..60 {
+            world.update();
+            heights.push(world.grains()[0].y);
+        }
+
+        let falls: Vec<usize> = heights.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert_eq!(falls[..6], [1, 1, 2, 2, 2, 3]);
+        assert!(falls.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(falls[falls.len() - 1], TERMINAL_VELOCITY as usize);
+    }
+
+    #[test]
+    fn fast_grains_dont_go_through_others() {
+        let mut world = World::with_grains(
+            1,
+            100,
+            0,
+            vec![
+                Sand::new(0, 0, Material::Sand),
+                Sand::new(0, 60, Material::Stone),
+            ],
+        );
+        for _ in 0..40 {
+            world.update();
+        }
+        assert_eq!(world.get(0, 59), Some(Material::Sand));
+        assert_eq!(world.grain_count(), 2);
+    }
+
+    #[test]
+    fn grains_never_move_faster_than_terminal_velocity() {
+        let mut world = World::with_grains(60, 60, 0, vec![Sand::new(30, 0, Material::Sand)]);
+        let cell = world.cells[30].as_mut().unwrap();
+        cell.velocity = (1e9, f32::INFINITY);
+        world.update();
+
+        let sand = &world.grains()[0];
+        assert!(sand.x - 30 <= TERMINAL_VELOCITY as usize);
+        assert!(sand.y <= TERMINAL_VELOCITY as usize);
+    }
+
+    #[test]
+    fn grains_splash_when_they_land_fast() {
+        // A short fall is too slow to splash
+        let mut world = World::with_grains(21, 5, 0, vec![Sand::new(10, 0, Material::Sand)]);
+        for _ in 0..20 {
+            world.update();
+        }
+        assert_eq!(world.grains()[0].x, 10);
+
+        let mut world = World::with_grains(21, 60, 0, vec![Sand::new(10, 0, Material::Sand)]);
+        for _ in 0..40 {
+            world.update();
+        }
+        let sand = &world.grains()[0];
+        assert_eq!(sand.y, 59);
+        assert_ne!(sand.x, 10);
+    }
+
+    #[test]
+    fn brush_strokes_and_eraser() {
+        let mut world = World::new(12, 6, 0);
+        world.brush.size = 1;
+        world.stroke((0, 0), (11, 5), false);
+        world.brush.size = 3;
+        world.brush.material = Material::Stone;
+        world.stroke((11, 0), (11, 0), false);
+        world.erase(5, 2);
+        assert_snapshot!(materials(&world), @r###"
+        ss........##
+        ..ss......##
+        ............
+        .......s....
+        ........ss..
+        ..........ss
+        "###);
+    }
+
+    #[test]
+    fn spray_fills_part_of_the_brush() {
+        let mut world = World::new(20, 20, 0);
+        world.brush.size = 20;
+        world.brush.spray = true;
+        world.paint(10, 10);
+        let grains = world.grain_count();
+        assert!((10..100).contains(&grains), "{grains} grains sprayed");
+    }
+
+    // Several chunks wide, so that the chunks are updated on several threads
+    fn rain(seed: u64) -> World {
+        let mut world = World::new(300, 200, seed);
+        world.brush.size = 8;
+        world.brush.spray = true;
+        for tick in 0..100 {
+            world.paint(20 + tick * 2, 10);
+            world.update();
+        }
+        for _ in 0..400 {
+            world.update();
+        }
+        world
+    }
+
+    #[test]
+    fn chunked_updates_are_deterministic() {
+        let (first, second) = (rain(5), rain(5));
+        assert!(first.grain_count() > 100);
+        assert_eq!(first.grains(), second.grains());
+    }
+
+    #[test]
+    fn settled_chunks_fall_asleep() {
+        let mut world = rain(1);
+        assert_eq!(world.awake_chunks(), 0);
+        let grains = world.grains();
+        world.update();
+        assert_eq!(world.grains(), grains);
+
+        // A grain dropped in the sky wakes its chunk, and the ones it falls into
+        world.add(Sand::new(150, 0, Material::Sand));
+        world.update();
+        assert_eq!(world.awake_chunks(), 1);
+        for _ in 0..200 {
+            world.update();
+        }
+        assert_eq!(world.awake_chunks(), 0);
+        assert_eq!(world.get(150, 0), None);
+    }
+
+    #[test]
+    fn only_the_changed_chunks_are_drawn() {
+        let mut world = World::with_grains(200, 100, 0, vec![Sand::new(10, 99, Material::Stone)]);
+        let mut buffer = WindowBuffer::new(200, 100);
+        world.display(&mut buffer);
+        let stone = world.reactions()[Material::Stone].color();
+        assert_eq!(buffer[(10, 99)], stone);
+
+        // Painted over the window, outside of the world
+        buffer[(150, 10)] = 1;
+        buffer[(20, 99)] = 1;
+        world.add(Sand::new(30, 99, Material::Stone));
+        world.display(&mut buffer);
+        assert_eq!(buffer[(150, 10)], 1);
+        assert_eq!(buffer[(20, 99)], 0);
+        assert_eq!(buffer[(30, 99)], stone);
+    }
+
+    #[test]
+    fn shapes_are_drawn_on_release() {
+        let mut world = World::new(8, 5, 0);
+        world.brush.size = 1;
+        world.brush.material = Material::Wall;
+        let mut input = Input {
+            keys: vec![TOOL_KEY, TOOL_KEY, TOOL_KEY],
+            ..Input::default()
+        };
+        world.handle_input(&input);
+        assert_eq!(world.brush.tool, Tool::Ramp);
+
+        input.keys.clear();
+        input.left = true;
+        for mouse in [(0, 1), (3, 2), (7, 3)] {
+            input.mouse = Some((mouse.0, mouse.1));
+            world.handle_input(&input);
+            assert_eq!(world.grain_count(), 0);
+        }
+        input.left = false;
+        world.handle_input(&input);
+        assert_snapshot!(materials(&world), @r###"
+        ........
+        @@......
+        @@@@@@..
+        @@@@@@@@
+        ........
+        "###);
+    }
+
+    #[test]
+    fn sand_piles_against_walls() {
+        let mut world = World::new(12, 8, 0);
+        world.brush.material = Material::Wall;
+        world.brush.size = 1;
+        world.brush.tool = Tool::Line;
+        let brush = world.brush.clone();
+        world.draw(&brush, (2, 7), (2, 3), false);
+        world.add_emitter(Emitter {
+            width: 1,
+            rate: 1.0,
+            ..Emitter::new(0, 0, Material::Sand)
+        });
+        for _ in 0..100 {
+            world.update();
+        }
+        // The walls don't move, and the sand can't go through them
+        assert_snapshot!(materials(&world), @r###"
+        s...........
+        ss..........
+        sss.........
+        ss@s........
+        ss@ss.......
+        ss@sss......
+        ss@ssss.....
+        ss@sssss....
+        "###);
+    }
+
+    #[test]
+    fn drains_swallow_what_falls_in() {
+        let mut world = World::new(5, 4, 0);
+        for x in 0..5 {
+            world.add(Sand::new(x, 3, Material::Drain));
+        }
+        world.add(Sand::new(2, 0, Material::Water));
+        world.add(Sand::new(0, 2, Material::Stone));
+        for _ in 0..10 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        .....
+        #....
+        vvvvv
+        "###);
+    }
+
+    #[test]
+    fn conveyors_carry_grains_and_fans_blow_them_up() {
+        let mut world = World::new(12, 12, 0);
+        world.brush.material = Material::Conveyor;
+        world.brush.size = 1;
+        world.brush.tool = Tool::Line;
+        let brush = world.brush.clone();
+        world.draw(&brush, (0, 11), (7, 11), false);
+        world.brush.rotate();
+        world.brush.rotate();
+        assert_eq!(world.brush.direction, Direction::Up);
+        let brush = world.brush.clone();
+        world.draw(&brush, (8, 11), (11, 11), false);
+
+        world.add(Sand::new(1, 10, Material::Sand));
+        let mut highest = 11;
+        for _ in 0..20 {
+            world.update();
+            let grain = world
+                .grains()
+                .into_iter()
+                .find(|sand| sand.material == Material::Sand);
+            highest = highest.min(grain.unwrap().y);
+        }
+        // Carried to the right onto the fans, then blown up
+        assert!(highest < 6, "the grain only went up to {highest}");
+    }
+
+    #[test]
+    fn emitters_are_placed_with_the_brush() {
+        let mut world = World::new(10, 10, 0);
+        world.brush.material = Material::Water;
+        world.brush.size = 2;
+        let mut input = Input {
+            mouse: Some((4, 0)),
+            keys: vec![EMITTER_KEY],
+            ..Input::default()
+        };
+        world.handle_input(&input);
+        assert_eq!(
+            world.emitters(),
+            &[Emitter {
+                width: 2,
+                ..Emitter::new(4, 0, Material::Water)
+            }]
+        );
+
+        for _ in 0..10 {
+            world.update();
+        }
+        assert!(world.get(4, 9) == Some(Material::Water));
+        let mut buffer = WindowBuffer::new(10, 10);
+        world.display(&mut buffer);
+        let spout = shade(world.reactions()[Material::Water].color(), 0.5);
+        assert_eq!(buffer[(4, 0)], spout);
+
+        // On the spout, the key takes the emitter away
+        input.mouse = Some((3, 0));
+        world.handle_input(&input);
+        assert!(world.emitters().is_empty());
+        world.display(&mut buffer);
+        assert_ne!(buffer[(4, 0)], spout);
+    }
+
+    #[test]
+    fn title_counts_the_particles() {
+        let mut world = World::new(10, 10, 0);
+        world.add(Sand::new(0, 0, Material::Sand));
+        world.add(Sand::new(1, 0, Material::Sand));
+        world.add(Sand::new(2, 0, Material::Water));
+        world.add(Sand::new(3, 0, Material::Wall));
+        let mut game = Game::new(world, Cli::parse_from(["grains_de_sable"]), None);
+        assert_snapshot!(game.title(), @"Grains de sable - 2 sand, 1 water - ESC to exit");
+
+        game.speed = 4.0;
+        assert_snapshot!(game.title(), @"Grains de sable - 2 sand, 1 water - x4 - ESC to exit");
+        game.paused = true;
+        game.world.clear();
+        assert_snapshot!(game.title(), @"Grains de sable - paused - ESC to exit");
+    }
+
+    #[test]
+    fn sand_is_sand_coloured_unless_it_is_a_rainbow() {
+        let mut world = World::new(20, 10, 0);
+        world.brush.size = 5;
+        let mut input = Input {
+            mouse: Some((10, 2)),
+            left: true,
+            ..Input::default()
+        };
+        world.handle_input(&input);
+        let colors: HashSet<u32> = world
+            .grains()
+            .iter()
+            .filter_map(|sand| sand.color)
+            .collect();
+        assert!(colors.len() > 5);
+        assert!(colors.iter().all(|color| {
+            let (red, green, blue) = channels(*color);
+            red > green && green > blue
+        }));
+
+        // Released, so that the next stroke doesn't start from here
+        world.handle_input(&Input::default());
+        input.keys = vec![RAINBOW_KEY];
+        input.mouse = Some((3, 7));
+        world.handle_input(&input);
+        assert!(world.rainbow());
+        let rainbow = world
+            .grains()
+            .iter()
+            .filter(|sand| sand.y > 4)
+            .filter_map(|sand| sand.color.map(channels))
+            .collect::<Vec<_>>();
+        // The rainbow starts red and goes to yellow
+        assert!(rainbow.contains(&(u8::MAX, 0, 0)));
+        assert!(rainbow.iter().any(|(_, green, _)| *green > 0));
+    }
+
+    #[test]
+    fn bodies_land_on_sand_and_sand_piles_on_them() {
+        let mut world = World::new(12, 12, 0);
+        for x in 0..12 {
+            for y in 9..12 {
+                world.add(Sand::new(x, y, Material::Sand));
+            }
+        }
+        world.add_body(Body::new(5, 2, Shape::Square, 4, Material::Stone));
+        for _ in 0..20 {
+            world.update();
+        }
+        assert_eq!(world.bodies()[0].y, 7);
+
+        for _ in 0..3 {
+            world.add(Sand::new(5, 0, Material::Sand));
+            world.update();
+            world.update();
+        }
+        assert_eq!(world.counts(), vec![(Material::Sand, 39)]);
+        for _ in 0..20 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ............
+        ............
+        ............
+        ............
+        ....ss......
+        ...####.....
+        ...####.....
+        ...####.....
+        ...####s....
+        ssssssssssss
+        ssssssssssss
+        ssssssssssss
+        "###);
+    }
+
+    #[test]
+    fn bodies_sink_in_water_and_push_it_up() {
+        let pool = || {
+            let mut world = World::new(8, 10, 0);
+            for x in 0..8 {
+                for y in 6..10 {
+                    world.add(Sand::new(x, y, Material::Water));
+                }
+            }
+            world
+        };
+        // Dropped on the water, the part under the surface pushes it away
+        let mut world = pool();
+        assert!(world.add_body(Body::new(4, 6, Shape::Circle, 4, Material::Stone)));
+        for _ in 0..40 {
+            world.update();
+        }
+        assert_eq!(world.counts(), vec![(Material::Water, 32)]);
+        assert_snapshot!(materials(&world), @r###"
+        ........
+        ........
+        ........
+        ........
+        ww.w..w.
+        wwwwwwww
+        www##www
+        ww####ww
+        ww####ww
+        www##www
+        "###);
+
+        // Wood is lighter than water, it stays on top
+        let mut world = pool();
+        assert!(world.add_body(Body::new(4, 2, Shape::Circle, 4, Material::Wood)));
+        for _ in 0..40 {
+            world.update();
+        }
+        assert_eq!(world.counts(), vec![(Material::Water, 32)]);
+        assert_snapshot!(materials(&world), @r###"
+        ........
+        ........
+        ...==...
+        ..====..
+        ..====..
+        ...==...
+        wwwwwwww
+        wwwwwwww
+        wwwwwwww
+        wwwwwwww
+        "###);
+    }
+
+    #[test]
+    fn bodies_never_replace_walls_nor_lose_grains() {
+        let mut world = World::new(8, 8, 0);
+        for x in 0..8 {
+            world.add(Sand::new(x, 6, Material::Wall));
+        }
+        // On a wall or on another body there is no room
+        assert!(!world.add_body(Body::new(4, 6, Shape::Square, 3, Material::Stone)));
+        assert!(world.add_body(Body::new(4, 4, Shape::Square, 3, Material::Stone)));
+        assert!(!world.add_body(Body::new(4, 3, Shape::Square, 3, Material::Wood)));
+        assert_eq!(world.bodies().len(), 1);
+
+        // Nor when the grains in the way can't go above it
+        let mut world = World::new(8, 4, 0);
+        for x in 0..8 {
+            for y in 1..4 {
+                world.add(Sand::new(x, y, Material::Sand));
+            }
+        }
+        assert!(!world.add_body(Body::new(4, 2, Shape::Square, 3, Material::Stone)));
+        assert!(world.bodies().is_empty());
+        assert_eq!(world.counts(), vec![(Material::Sand, 24)]);
+    }
+
+    #[test]
+    fn bodies_are_dropped_with_the_brush() {
+        let mut world = World::new(20, 20, 0);
+        world.brush.shape = Shape::Circle;
+        world.brush.size = 5;
+        world.handle_input(&Input {
+            mouse: Some((10, 5)),
+            keys: vec![BODY_KEY],
+            ..Input::default()
+        });
+        // Sand isn't solid, the ball is made of wood
+        assert_eq!(
+            world.bodies(),
+            &[Body::new(10, 5, Shape::Circle, 5, Material::Wood)]
+        );
+        world.update();
+        assert!(world.bodies()[0].y > 5);
+
+        // Once it is erased it's gone
+        world.brush.size = 20;
+        world.erase(10, 10);
+        world.update();
+        assert!(world.bodies().is_empty());
+    }
+
+    #[test]
+    fn clearing_empties_the_world() {
+        let mut world = World::new(20, 20, 0);
+        Level::Hourglass.build(&mut world);
+        world.add_body(Body::new(10, 15, Shape::Square, 3, Material::Stone));
+        world.update();
+        let mut buffer = WindowBuffer::new(20, 20);
+        world.display(&mut buffer);
+
+        world.handle_input(&Input {
+            keys: vec![CLEAR_KEY],
+            ..Input::default()
+        });
+        world.update();
+        assert_eq!(world.grain_count(), 0);
+        assert!(world.emitters().is_empty() && world.bodies().is_empty());
+        world.display(&mut buffer);
+        assert!(buffer.buffer().iter().all(|color| *color == 0));
+    }
+
+    #[test]
+    fn a_factory_runs_forever() {
+        // An emitter pours on a belt that ends in a drain: as many grains come as go
+        let mut world = World::new(40, 20, 0);
+        world.brush.material = Material::Conveyor;
+        world.brush.size = 2;
+        world.brush.tool = Tool::Line;
+        let brush = world.brush.clone();
+        world.draw(&brush, (0, 19), (33, 19), false);
+        world.brush.material = Material::Drain;
+        let brush = world.brush.clone();
+        world.draw(&brush, (36, 19), (39, 19), false);
+        world.add_emitter(Emitter {
+            rate: 0.2,
+            width: 1,
+            ..Emitter::new(5, 2, Material::Sand)
+        });
+
+        let mut counts = Vec::new();
+        for tick in 0..2000 {
+            world.update();
+            if tick % 500 == 499 {
+                counts.push(world.counts());
+            }
+        }
+        let sand: Vec<usize> = counts.iter().map(|counts| counts[0].1).collect();
+        let (least, most) = (sand.iter().min().unwrap(), sand.iter().max().unwrap());
+        assert!(most - least < 20, "the sand piles up: {sand:?}");
+        assert_snapshot!(materials(&world), @r###"
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        .....s..................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        ........................................
+        .....s..................................
+        ...................s...s..s..s.....s....
+        >>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>.vvvvv
+        >>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>.vvvvv
+        "###);
+    }
+
+    #[test]
+    fn lava_turns_to_stone_and_boils_water() {
+        let mut world = World::new(6, 8, 0);
+        for x in 0..6 {
+            world.add(Sand::new(x, 7, Material::Lava));
+            world.add(Sand::new(x, 6, Material::Water));
+            world.add(Sand::new(x, 5, Material::Water));
+        }
+        for _ in 0..3 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ......
+        ......
+        ......
+        ......
+        ::::::
+        ......
+        ::::::
+        ******
+        "###);
+
+        // The steam goes up and the lava, cooled down by the water, is now stone
+        for _ in 0..400 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ::::::
+        ::::::
+        ......
+        ......
+        ......
+        ......
+        ......
+        ######
+        "###);
+    }
+
+    #[test]
+    fn heat_spreads_and_melts_sand_into_glass() {
+        let mut world = World::new(5, 4, 0);
+        for x in 0..5 {
+            world.add(Sand::new(x, 3, Material::Lava));
+            world.add(Sand::new(x, 2, Material::Lava));
+        }
+        world.add(Sand::new(3, 0, Material::Sand));
+        assert_eq!(world.temperature(3, 0), Some(20.0));
+        world.update();
+        world.update();
+        assert!(world.temperature(3, 1).unwrap() > 100.0);
+
+        for _ in 0..40 {
+            world.update();
+        }
+        // The grain resting on the lava melted into glass
+        assert_snapshot!(materials(&world), @r###"
+        .....
+        ...+.
+        *****
+        *****
+        "###);
+    }
+
+    // The bundled table without its reactions
+    fn bundled_materials() -> &'static str {
+        let bundled = include_str!("../reactions.toml");
+        &bundled[..bundled.find("[[reactions]]").unwrap()]
+    }
+
+    #[test]
+    fn reactions_come_from_the_table() {
+        let table = ReactionTable::parse(&format!(
+            r#"
+            {}
+
+            [[reactions]]
+            material = "sand"
+            touching = "water"
+            into = "stone"
+
+            [[reactions]]
+            material = "oil"
+            touching = "stone"
+            "#,
+            bundled_materials()
+        ))
+        .unwrap();
+        let mut world = World::new(3, 3, 0);
+        world.set_reactions(table);
+        world.add(Sand::new(0, 2, Material::Water));
+        world.add(Sand::new(1, 2, Material::Sand));
+        world.add(Sand::new(2, 2, Material::Oil));
+        world.add(Sand::new(2, 0, Material::Water));
+
+        world.update();
+        world.update();
+        // Only the reactions of the table happen
+        assert_snapshot!(materials(&world), @r###"
+        ...
+        ...
+        w#w
+        "###);
+    }
+
+    #[test]
+    fn a_new_material_is_one_table_entry() {
+        let table = ReactionTable::parse(&format!(
+            r#"
+            {}
+
+            [[materials]]
+            name = "mud"
+            movement = "powder"
+            density = 14
+            colors = [0x604830, 0x584028]
+            "#,
+            bundled_materials()
+        ))
+        .unwrap();
+        let mut world = World::new(3, 4, 0);
+        world.set_reactions(table);
+        world.add(Sand::new(1, 3, Material::Water));
+        world.add(Sand::new(0, 3, Material::Stone));
+        world.add(Sand::new(2, 3, Material::Stone));
+
+        // Every material after the built-in ones, then back to sand
+        let mut input = Input {
+            keys: vec![NEXT_MATERIAL_KEY],
+            ..Input::default()
+        };
+        while world.brush().material != Material::Glass {
+            world.handle_input(&input);
+        }
+        world.handle_input(&input);
+        let mud = world.brush().material;
+        assert_eq!(world.reactions()[mud].name, "mud");
+        input.keys.clear();
+        world.brush.size = 1;
+        input.mouse = Some((1, 0));
+        input.left = true;
+        world.handle_input(&input);
+
+        // It falls, sinks through the water and lies on the stones like any powder
+        for _ in 0..10 {
+            world.update();
+        }
+        assert_snapshot!(materials(&world), @r###"
+        ...
+        ...
+        w..
+        #m#
+        "###);
+        let game = Game::new(world, Cli::parse_from(["grains_de_sable"]), None);
+        assert_snapshot!(game.title(), @"Grains de sable - 1 water, 1 mud - ESC to exit");
+    }
+}
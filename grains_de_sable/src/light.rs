@@ -0,0 +1,134 @@
+use std::ops::Range;
+
+use pixel_sims::colour::{mix, shade};
+use window_rs::WindowBuffer;
+
+use crate::{Cell, World};
+
+// Grains get darker and darker that many cells under the surface of a pile or a pool, then
+// they stay as dark
+pub const DEPTH_REACH: usize = 12;
+// How dark the bottom of a pile is, 0 would be black
+const BOTTOM_SHADE: f32 = 0.55;
+// How far fire and lava light up what is around them, in cells
+pub const GLOW_RADIUS: usize = 3;
+// How much of the colour of the glow goes on a grain right next to it, and on the air
+const GLOW: f32 = 0.5;
+const AIR_GLOW: f32 = 0.3;
+
+/// Draws the cells of `columns` × `rows` with their light: darker the deeper they are in a
+/// pile, and lit by the fire and the lava around them.
+pub(crate) fn draw(
+    world: &World,
+    columns: Range<usize>,
+    rows: Range<usize>,
+    buffer: &mut WindowBuffer,
+) {
+    let width = columns.len();
+    let cell = |x: usize, y: usize| world.cells[y * world.width + x];
+    let reactions = &world.reactions;
+    // Only what falls piles up, fire and the gases neither cast a shadow nor get darker
+    let piles_up = |cell: Cell| reactions[cell.material].falls();
+
+    // The strongest glow reaching each cell, and its colour
+    let mut glow = vec![(0.0, 0); width * rows.len()];
+    let around = |range: &Range<usize>, end: usize| {
+        range.start.saturating_sub(GLOW_RADIUS)..(range.end + GLOW_RADIUS).min(end)
+    };
+    for y in around(&rows, world.height) {
+        for x in around(&columns, world.width) {
+            let Some(source) = cell(x, y).filter(|cell| reactions[cell.material].glows) else {
+                continue;
+            };
+            for lit_y in around(&(y..y + 1), world.height) {
+                for lit_x in around(&(x..x + 1), world.width) {
+                    if !columns.contains(&lit_x) || !rows.contains(&lit_y) {
+                        continue;
+                    }
+                    let distance = (lit_x.abs_diff(x).pow(2) + lit_y.abs_diff(y).pow(2)) as f32;
+                    let strength = 1.0 - distance.sqrt() / (GLOW_RADIUS + 1) as f32;
+                    let lit = &mut glow[(lit_y - rows.start) * width + lit_x - columns.start];
+                    if strength > lit.0 {
+                        *lit = (strength, reactions[source.material].color());
+                    }
+                }
+            }
+        }
+    }
+
+    for x in columns.clone() {
+        // How many grains are piled up over the cell, counted from above the drawn rows
+        let mut depth = 0;
+        for y in rows.start.saturating_sub(DEPTH_REACH)..rows.end {
+            let here = cell(x, y);
+            if rows.contains(&y) {
+                let (strength, glow_color) = glow[(y - rows.start) * width + x - columns.start];
+                buffer[(x, y)] = match here {
+                    None if strength > 0.0 => shade(glow_color, strength * AIR_GLOW),
+                    None => 0,
+                    Some(cell) if reactions[cell.material].glows => cell.color,
+                    Some(cell) => {
+                        let mut color = cell.color;
+                        if piles_up(cell) {
+                            let darkness = depth.min(DEPTH_REACH) as f32 / DEPTH_REACH as f32;
+                            color = shade(color, 1.0 - (1.0 - BOTTOM_SHADE) * darkness);
+                        }
+                        mix(color, glow_color, strength * GLOW)
+                    }
+                };
+            }
+            depth = match here {
+                Some(cell) if piles_up(cell) => depth + 1,
+                _ => 0,
+            };
+        }
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Material, Sand};
+
+    #[test]
+    fn test_piles_get_darker_with_depth() {
+        let mut world = World::new(3, 20, 0);
+        for y in 0..20 {
+            world.add(Sand::new(1, y, Material::Sand));
+        }
+        let mut buffer = WindowBuffer::new(3, 20);
+        world.display(&mut buffer);
+
+        let color = world.reactions()[Material::Sand].color();
+        assert_eq!(buffer[(1, 0)], color);
+        assert_eq!(
+            buffer[(1, 6)],
+            shade(color, 1.0 - (1.0 - BOTTOM_SHADE) * 0.5)
+        );
+        assert_eq!(buffer[(1, DEPTH_REACH)], shade(color, BOTTOM_SHADE));
+        assert_eq!(buffer[(1, 19)], shade(color, BOTTOM_SHADE));
+    }
+
+    #[test]
+    fn test_fire_glows() {
+        let mut world = World::new(9, 3, 0);
+        world.add(Sand::new(4, 1, Material::Fire));
+        world.add(Sand::new(5, 1, Material::Stone));
+        world.add(Sand::new(8, 1, Material::Stone));
+        let mut buffer = WindowBuffer::new(9, 3);
+        world.display(&mut buffer);
+
+        let stone = world.reactions()[Material::Stone].color();
+        let fire = world.reactions()[Material::Fire].color();
+        assert_eq!(buffer[(4, 1)], fire);
+        assert_eq!(buffer[(5, 1)], mix(stone, fire, 0.75 * GLOW));
+        // Out of reach
+        assert_eq!(buffer[(8, 1)], stone);
+        assert_eq!(buffer[(0, 1)], 0);
+        // The air around the fire is lit, less and less far from it
+        assert_eq!(buffer[(3, 1)], shade(fire, 0.75 * AIR_GLOW));
+        assert_eq!(buffer[(2, 1)], shade(fire, 0.5 * AIR_GLOW));
+    }
+}
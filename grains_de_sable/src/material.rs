@@ -0,0 +1,209 @@
+use std::fmt;
+
+use graphic::Key;
+use pixel_sims::colour::shade;
+use rand::Rng;
+use serde::Deserialize;
+
+// How much brighter or darker than its palette a particle can be
+const JITTER: f32 = 0.08;
+
+/// A material of the reaction table, by its place in the table. How it moves, how heavy it
+/// is and its colours are in the table; the built-in materials come first and keep their
+/// place, the ones the table adds come after them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Material(pub(crate) u8);
+
+// Named like the variants they used to be, so that they read the same in a `match`
+#[allow(non_upper_case_globals)]
+impl Material {
+    pub const Sand: Material = Material(0);
+    pub const Water: Material = Material(1);
+    pub const Stone: Material = Material(2);
+    /// Burns.
+    pub const Wood: Material = Material(3);
+    /// Sets what is flammable around it on fire, then goes up in smoke.
+    pub const Fire: Material = Material(4);
+    pub const Smoke: Material = Material(5);
+    pub const Oil: Material = Material(6);
+    /// The geometry of the levels.
+    pub const Wall: Material = Material(7);
+    /// Swallows the particles that touch it.
+    pub const Drain: Material = Material(8);
+    /// Pushes the particle lying on it, sideways like a belt or up like a fan.
+    pub const Conveyor: Material = Material(9);
+    pub const Steam: Material = Material(10);
+    pub const Lava: Material = Material(11);
+    pub const Glass: Material = Material(12);
+
+    /// The names of the built-in materials, in the order of their index. The game counts on
+    /// them, every reaction table has to give them.
+    pub const BUILT_IN: [&'static str; 13] = [
+        "sand", "water", "stone", "wood", "fire", "smoke", "oil", "wall", "drain", "conveyor",
+        "steam", "lava", "glass",
+    ];
+
+    /// Its place in the reaction table.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The material picked by a key: the number keys, `1` is sand and `0` comes last, and
+    /// `V` for lava. Steam and glass only come out of reactions.
+    pub fn from_key(key: Key) -> Option<Material> {
+        let material = match key {
+            Key::Key1 => Material::Sand,
+            Key::Key2 => Material::Water,
+            Key::Key3 => Material::Stone,
+            Key::Key4 => Material::Wood,
+            Key::Key5 => Material::Fire,
+            Key::Key6 => Material::Smoke,
+            Key::Key7 => Material::Oil,
+            Key::Key8 => Material::Wall,
+            Key::Key9 => Material::Drain,
+            Key::Key0 => Material::Conveyor,
+            Key::V => Material::Lava,
+            _ => return None,
+        };
+        Some(material)
+    }
+}
+
+// The name of the built-in materials, the others only have one in their table
+impl fmt::Debug for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Material::BUILT_IN.get(self.index()) {
+            Some(name) => write!(f, "{}{}", name[..1].to_uppercase(), &name[1..]),
+            None => write!(f, "Material({})", self.0),
+        }
+    }
+}
+
+/// How the particles of a material move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Movement {
+    /// Stays where it is drawn, the bodies can't go through it.
+    Solid,
+    /// Falls and piles up.
+    Powder,
+    /// Falls and flows sideways until it finds its level.
+    Liquid,
+    /// Rises, and flows sideways under a ceiling.
+    Gas,
+    /// Stays where it is, but the drains swallow it and the bodies push it away, like fire.
+    Still,
+}
+
+/// What a material is, one entry of the reaction table.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Properties {
+    pub name: String,
+    pub movement: Movement,
+    /// Heavier grains sink through lighter fluids, 255 is for the geometry of the levels.
+    pub density: u8,
+    /// A few shades of the material, each new particle takes one of them.
+    pub colors: Vec<u32>,
+    /// How many ticks a particle lives, 0 for the ones that last forever.
+    #[serde(default)]
+    pub life: u8,
+    #[serde(default)]
+    pub flammable: bool,
+    /// Lights up what is around it.
+    #[serde(default)]
+    pub glows: bool,
+    /// Of the new particles, the ambient temperature when it isn't given.
+    pub temperature: Option<f32>,
+    /// Share of the difference of temperature with a neighbour that goes through on each
+    /// tick, from 0 to 1.
+    #[serde(default)]
+    pub conductivity: f32,
+}
+
+impl Properties {
+    /// The materials that stay where they are drawn.
+    pub fn is_static(&self) -> bool {
+        self.movement == Movement::Solid
+    }
+
+    /// Fluids make way for the grains denser than them.
+    pub fn is_fluid(&self) -> bool {
+        matches!(self.movement, Movement::Liquid | Movement::Gas)
+    }
+
+    /// Falls, and piles up or pools.
+    pub fn falls(&self) -> bool {
+        matches!(self.movement, Movement::Powder | Movement::Liquid)
+    }
+
+    /// The colour the material is known by, the first of its palette. PNG levels are
+    /// written and read with it.
+    pub fn color(&self) -> u32 {
+        self.colors[0]
+    }
+
+    /// A colour of the palette picked at random, a bit brighter or darker so that no two
+    /// grains look the same. The geometry of the levels keeps its plain colour.
+    pub fn random_color(&self, rng: &mut impl Rng) -> u32 {
+        let color = self.colors[rng.gen_range(0..self.colors.len())];
+        if self.density == u8::MAX {
+            color
+        } else {
+            shade(color, rng.gen_range(1.0 - JITTER..=1.0 + JITTER))
+        }
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ReactionTable;
+    use pixel_sims::colour::channels;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_palette_keys() {
+        assert_eq!(Material::from_key(Key::Key1), Some(Material::Sand));
+        assert_eq!(Material::from_key(Key::Key6), Some(Material::Smoke));
+        assert_eq!(Material::from_key(Key::Key7), Some(Material::Oil));
+        assert_eq!(Material::from_key(Key::Key8), Some(Material::Wall));
+        assert_eq!(Material::from_key(Key::Key9), Some(Material::Drain));
+        assert_eq!(Material::from_key(Key::Key0), Some(Material::Conveyor));
+        assert_eq!(Material::from_key(Key::V), Some(Material::Lava));
+        assert_eq!(Material::from_key(Key::A), None);
+    }
+
+    #[test]
+    fn test_debug_names() {
+        assert_eq!(format!("{:?}", Material::Conveyor), "Conveyor");
+        assert_eq!(format!("{:?}", Material(20)), "Material(20)");
+    }
+
+    #[test]
+    fn test_random_colors_stay_close_to_the_palette() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for properties in ReactionTable::default().properties() {
+            let colors: HashSet<u32> = (0..50).map(|_| properties.random_color(&mut rng)).collect();
+            for color in &colors {
+                let (red, green, blue) = channels(*color);
+                let close = properties.colors.iter().any(|base| {
+                    let (r, g, b) = channels(*base);
+                    [(red, r), (green, g), (blue, b)].iter().all(|(c, base)| {
+                        (*c as f32 - *base as f32).abs() <= *base as f32 * JITTER + 1.0
+                    })
+                });
+                assert!(close, "{} got {color:06x}", properties.name);
+            }
+            if properties.density == u8::MAX {
+                assert_eq!(colors.len(), 1, "{}", properties.name);
+            } else {
+                assert!(colors.len() > 5, "{}", properties.name);
+            }
+        }
+    }
+}
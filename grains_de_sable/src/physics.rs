@@ -0,0 +1,521 @@
+use std::marker::PhantomData;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::chunk::{Chunks, CHUNK_SIZE};
+use crate::material::Movement;
+use crate::reaction::ReactionTable;
+use crate::{
+    Cell, Material, CONVEYOR_SPEED, FAN_SPEED, FRICTION, GRAVITY, REST_VELOCITY, SPLASH_SPREAD,
+    SPLASH_VELOCITY, TERMINAL_VELOCITY,
+};
+
+// How far out of its chunk a particle can read or write on one tick: its fastest move, the
+// cell it checks after that, and a bit of margin. Heat and reactions only reach the next
+// cell. Chunks updated at the same time are a chunk apart, so they never reach the same
+// cells.
+const REACH: usize = 8;
+const _: () = assert!(TERMINAL_VELOCITY as usize + 2 <= REACH && 2 * REACH <= CHUNK_SIZE);
+// Changes of temperature smaller than that are dropped, or the chunks would never sleep
+const HEAT_THRESHOLD: f32 = 0.01;
+const _: () = assert!(CONVEYOR_SPEED <= TERMINAL_VELOCITY && FAN_SPEED <= TERMINAL_VELOCITY);
+
+/// The cells of the world, shared by the threads updating chunks at the same time.
+#[derive(Clone, Copy)]
+pub struct SharedCells<'a> {
+    cells: *mut Option<Cell>,
+    len: usize,
+    _cells: PhantomData<&'a mut [Option<Cell>]>,
+}
+
+// SAFETY: the threads only touch the cells within `REACH` of their own chunk, and the
+// chunks updated together are too far apart for those cells to overlap.
+unsafe impl Send for SharedCells<'_> {}
+unsafe impl Sync for SharedCells<'_> {}
+
+impl<'a> SharedCells<'a> {
+    pub fn new(cells: &'a mut [Option<Cell>]) -> Self {
+        Self {
+            cells: cells.as_mut_ptr(),
+            len: cells.len(),
+            _cells: PhantomData,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<Cell> {
+        assert!(index < self.len);
+        // SAFETY: in bounds, and no other thread writes this cell (see `REACH`)
+        unsafe { *self.cells.add(index) }
+    }
+
+    fn set(&self, index: usize, cell: Option<Cell>) {
+        assert!(index < self.len);
+        // SAFETY: in bounds, and no other thread reads or writes this cell (see `REACH`)
+        unsafe { *self.cells.add(index) = cell }
+    }
+}
+
+/// Updates the particles of one chunk, with its own random choices so that the result
+/// doesn't depend on which thread got which chunk.
+pub struct Updater<'a> {
+    pub cells: SharedCells<'a>,
+    pub chunks: &'a Chunks,
+    pub width: usize,
+    pub height: usize,
+    pub tick: u32,
+    pub rng: StdRng,
+    pub reactions: &'a ReactionTable,
+}
+
+impl Updater<'_> {
+    pub fn update_chunk(&mut self, chunk: (usize, usize)) {
+        let (columns, rows) = self.chunks.bounds(chunk);
+        let clock = self.tick % 2 == 1;
+
+        // On part du bas pour que les grains d'une même colonne tombent ensemble
+        for y in rows.clone().rev() {
+            // Une ligne sur deux, et d'une image à l'autre, on change de sens pour que les
+            // tas ne penchent pas d'un côté
+            let right_to_left = (y % 2 == 0) == clock;
+            for x in columns.clone() {
+                let x = if right_to_left {
+                    columns.end - 1 - (x - columns.start)
+                } else {
+                    x
+                };
+                let Some(cell) = self.cells.get(y * self.width + x) else {
+                    continue;
+                };
+                if cell.tick == self.tick {
+                    continue;
+                }
+
+                match cell.material {
+                    Material::Fire => self.burn(x, y),
+                    Material::Drain => self.drain(x, y),
+                    Material::Conveyor => self.push(x, y, cell.velocity),
+                    // The others move the way the reaction table tells
+                    material => {
+                        let properties = &self.reactions[material];
+                        if properties.life > 0 && !self.age(x, y) {
+                            continue;
+                        }
+                        match properties.movement {
+                            Movement::Powder | Movement::Liquid => self.fall(x, y, material),
+                            Movement::Gas => self.rise(x, y),
+                            Movement::Solid | Movement::Still => (),
+                        }
+                    }
+                }
+            }
+        }
+
+        // Once everything moved, the heat spreads and the particles react
+        for y in rows {
+            for x in columns.clone() {
+                self.exchange_heat(x, y);
+                self.react(x, y);
+            }
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<Material> {
+        if x < self.width && y < self.height {
+            self.cells.get(y * self.width + x).map(|cell| cell.material)
+        } else {
+            None
+        }
+    }
+
+    // Outside of the world counts as taken, grains can't go there
+    fn is_free(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.cells.get(y * self.width + x).is_none()
+    }
+
+    // A grain goes into an empty cell, or sinks through a lighter fluid by swapping with it
+    fn can_enter(&self, x: usize, y: usize, material: Material) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        match self.cells.get(y * self.width + x) {
+            None => true,
+            Some(cell) => {
+                let (cell, grain) = (&self.reactions[cell.material], &self.reactions[material]);
+                cell.is_fluid() && cell.density < grain.density
+            }
+        }
+    }
+
+    // Replaces a cell, and wakes up the chunks around it
+    fn set(&self, x: usize, y: usize, cell: Option<Cell>) {
+        self.cells.set(y * self.width + x, cell);
+        self.chunks.touch(x, y);
+    }
+
+    // Whatever was in the destination, a lighter fluid or nothing, takes the place of
+    // the grain
+    fn move_to(&mut self, (x, y): (usize, usize), (to_x, to_y): (usize, usize)) {
+        let (from, to) = (
+            self.cells.get(y * self.width + x),
+            self.cells.get(to_y * self.width + to_x),
+        );
+        let updated = |cell: Option<Cell>| {
+            cell.map(|cell| Cell {
+                tick: self.tick,
+                ..cell
+            })
+        };
+        self.set(x, y, updated(to));
+        self.set(to_x, to_y, updated(from));
+    }
+
+    // The cells left and right of x on the line y that `fits`
+    fn sides(
+        &self,
+        x: usize,
+        y: usize,
+        fits: impl Fn(usize, usize) -> bool,
+    ) -> (Option<usize>, Option<usize>) {
+        let left = x.checked_sub(1).filter(|left| fits(*left, y));
+        let right = Some(x + 1).filter(|right| fits(*right, y));
+        (left, right)
+    }
+
+    // One of the sides, picked at random when both are possible
+    fn pick(&mut self, sides: (Option<usize>, Option<usize>)) -> Option<usize> {
+        match sides {
+            (None, None) => None,
+            (Some(side), None) | (None, Some(side)) => Some(side),
+            (Some(left), Some(right)) => {
+                if self.rng.gen_bool(0.5) {
+                    Some(left)
+                } else {
+                    Some(right)
+                }
+            }
+        }
+    }
+
+    // Along its velocity, then down the slope, then sideways for the fluids
+    fn fall(&mut self, x: usize, y: usize, material: Material) {
+        let index = y * self.width + x;
+        let Some(mut cell) = self.cells.get(index) else {
+            return;
+        };
+
+        // A grain lying on something only moves sideways, unless a fan blew it up
+        if cell.velocity.1 < 0.0 || self.can_enter(x, y + 1, material) {
+            cell.velocity.1 = (cell.velocity.1 + GRAVITY).min(TERMINAL_VELOCITY);
+        } else {
+            cell.velocity.1 = 0.0;
+        }
+        let distance = (
+            cell.velocity.0 + cell.offset.0,
+            cell.velocity.1 + cell.offset.1,
+        );
+        let steps = (distance.0.trunc(), distance.1.floor());
+        cell.offset = (distance.0 - steps.0, distance.1 - steps.1);
+        if cell.velocity.1 == 0.0 {
+            cell.velocity.1 = REST_VELOCITY;
+        }
+        cell.velocity.0 *= FRICTION;
+        if cell.velocity.0.abs() < 0.5 {
+            cell.velocity.0 = 0.0;
+        }
+        // Only the speed changed, that doesn't keep the chunk awake
+        self.cells.set(index, Some(cell));
+
+        let steps = (steps.0 as isize, steps.1 as isize);
+        let ((to_x, to_y), blocked) = self.trace((x, y), steps, material);
+        if blocked {
+            self.land(to_x, to_y, material);
+        }
+        if (to_x, to_y) != (x, y) {
+            return;
+        }
+
+        let below = y + 1;
+        let sides = self.sides(x, below, |x, y| self.can_enter(x, y, material));
+        if let Some(side) = self.pick(sides) {
+            self.move_to((x, y), (side, below));
+            return;
+        }
+
+        // Fluids only spread into empty cells, or two of them would swap forever
+        if self.reactions[material].is_fluid() {
+            let sides = self.sides(x, y, |x, y| self.is_free(x, y));
+            if let Some(side) = self.pick(sides) {
+                self.move_to((x, y), (side, y));
+            }
+        }
+    }
+
+    // Moves the grain one cell at a time on the line to (x + dx, y + dy), so that a fast
+    // grain doesn't go through the others. Tells where it stopped and if it was blocked.
+    // A move is never longer than the terminal velocity, whatever the velocity says, or it
+    // would get out of `REACH`.
+    fn trace(
+        &mut self,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+        material: Material,
+    ) -> ((usize, usize), bool) {
+        let fastest = TERMINAL_VELOCITY as isize;
+        let (dx, dy) = (dx.clamp(-fastest, fastest), dy.clamp(-fastest, fastest));
+        let steps = dx.abs().max(dy.abs());
+        let mut current = (x, y);
+
+        for step in 1..=steps {
+            let progress = step as f32 / steps as f32;
+            let next_x = x as isize + (dx as f32 * progress).round() as isize;
+            let next_y = y as isize + (dy as f32 * progress).round() as isize;
+            if next_x < 0 || next_y < 0 {
+                return (current, true);
+            }
+
+            let next = (next_x as usize, next_y as usize);
+            if !self.can_enter(next.0, next.1, material) {
+                return (current, true);
+            }
+            self.move_to(current, next);
+            current = next;
+        }
+
+        (current, false)
+    }
+
+    // The grain hit something: it stops, and splashes if it was falling fast enough
+    fn land(&mut self, x: usize, y: usize, material: Material) {
+        let index = y * self.width + x;
+        let Some(mut cell) = self.cells.get(index) else {
+            return;
+        };
+
+        if !self.can_enter(x, y + 1, material) {
+            if cell.velocity.1 >= SPLASH_VELOCITY {
+                let spread = cell.velocity.1 * SPLASH_SPREAD;
+                cell.velocity.0 = if self.rng.gen_bool(0.5) {
+                    spread
+                } else {
+                    -spread
+                };
+            }
+            cell.velocity.1 = REST_VELOCITY;
+            cell.offset.1 = 0.0;
+        }
+
+        // Against a wall or another grain
+        let side = x as isize + cell.velocity.0.signum() as isize;
+        if cell.velocity.0 != 0.0 && (side < 0 || !self.can_enter(side as usize, y, material)) {
+            cell.velocity.0 = 0.0;
+            cell.offset.0 = 0.0;
+        }
+
+        self.cells.set(index, Some(cell));
+    }
+
+    // Like `fall` upside down
+    fn rise(&mut self, x: usize, y: usize) {
+        if let Some(above) = y.checked_sub(1) {
+            if self.is_free(x, above) {
+                self.move_to((x, y), (x, above));
+                return;
+            }
+
+            let sides = self.sides(x, above, |x, y| self.is_free(x, y));
+            if let Some(side) = self.pick(sides) {
+                self.move_to((x, y), (side, above));
+                return;
+            }
+        }
+
+        let sides = self.sides(x, y, |x, y| self.is_free(x, y));
+        if let Some(side) = self.pick(sides) {
+            self.move_to((x, y), (side, y));
+        }
+    }
+
+    fn burn(&mut self, x: usize, y: usize) {
+        for (nx, ny) in neighbours(x, y) {
+            if self
+                .get(nx, ny)
+                .is_some_and(|material| self.reactions[material].flammable)
+            {
+                let fire = self.spawn(Material::Fire);
+                self.set(nx, ny, Some(fire));
+            }
+        }
+
+        if !self.age(x, y) {
+            let smoke = self.spawn(Material::Smoke);
+            self.set(x, y, Some(smoke));
+        }
+    }
+
+    // The particles that move vanish when they touch a drain
+    fn drain(&mut self, x: usize, y: usize) {
+        for (nx, ny) in neighbours(x, y) {
+            if self
+                .get(nx, ny)
+                .is_some_and(|material| !self.reactions[material].is_static())
+            {
+                self.set(nx, ny, None);
+            }
+        }
+    }
+
+    // The velocity of a conveyor is the push it gives to the grain lying on it, the next
+    // move of the grain follows it
+    fn push(&mut self, x: usize, y: usize, (sideways, upwards): (f32, f32)) {
+        let Some(above) = y.checked_sub(1) else {
+            return;
+        };
+        let index = above * self.width + x;
+        let Some(mut cell) = self.cells.get(index) else {
+            return;
+        };
+        if !self.reactions[cell.material].falls() {
+            return;
+        }
+
+        if sideways != 0.0 {
+            cell.velocity.0 = sideways;
+        }
+        if upwards < 0.0 {
+            cell.velocity.1 = upwards;
+        }
+        self.set(x, above, Some(cell));
+    }
+
+    // A new particle, as hot as its material makes them
+    fn spawn(&mut self, material: Material) -> Cell {
+        let color = self.reactions[material].random_color(&mut self.rng);
+        Cell::new(material, color, self.tick, self.reactions)
+    }
+
+    // The particle shares its heat with the ones on its right and below, the others do it
+    // with it, and with the air on its free sides
+    fn exchange_heat(&mut self, x: usize, y: usize) {
+        let index = y * self.width + x;
+        let Some(mut cell) = self.cells.get(index) else {
+            return;
+        };
+        let conductivity = self.reactions.conductivity(cell.material);
+        if conductivity == 0.0 {
+            return;
+        }
+        let before = cell.temperature;
+
+        for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+            if nx >= self.width || ny >= self.height {
+                continue;
+            }
+            let Some(mut other) = self.cells.get(ny * self.width + nx) else {
+                continue;
+            };
+            let conductivity = conductivity.min(self.reactions.conductivity(other.material));
+            // A quarter at most, so that a cell doesn't give more than it has to its four
+            // neighbours
+            let flow = (other.temperature - cell.temperature) * conductivity / 4.0;
+            if flow.abs() > HEAT_THRESHOLD {
+                cell.temperature += flow;
+                other.temperature -= flow;
+                self.set(nx, ny, Some(other));
+            }
+        }
+
+        let air = neighbours(x, y)
+            .into_iter()
+            .filter(|(x, y)| self.is_free(*x, *y))
+            .count();
+        let conductivity = conductivity.min(self.reactions.air_conductivity);
+        let flow = (self.reactions.ambient - cell.temperature) * conductivity / 4.0 * air as f32;
+        if flow.abs() > HEAT_THRESHOLD {
+            cell.temperature += flow;
+        }
+
+        if cell.temperature != before {
+            self.set(x, y, Some(cell));
+        }
+    }
+
+    // The first reaction of the table whose conditions are met
+    fn react(&mut self, x: usize, y: usize) {
+        let Some(cell) = self.cells.get(y * self.width + x) else {
+            return;
+        };
+
+        let reactions = self.reactions;
+        for reaction in reactions.of(cell.material) {
+            if reaction
+                .above
+                .is_some_and(|above| cell.temperature <= above)
+                || reaction
+                    .below
+                    .is_some_and(|below| cell.temperature >= below)
+            {
+                continue;
+            }
+            let partner = match reaction.touching {
+                Some(material) => {
+                    let found = neighbours(x, y)
+                        .into_iter()
+                        .find(|(nx, ny)| self.get(*nx, *ny) == Some(material));
+                    if found.is_none() {
+                        continue;
+                    }
+                    found
+                }
+                None => None,
+            };
+            if reaction.chance < 1.0 && !self.rng.gen_bool(reaction.chance) {
+                continue;
+            }
+
+            let turned = reaction.into.map(|into| self.turn(cell, into));
+            self.set(x, y, turned);
+            if let (Some((px, py)), Some(into)) = (partner, reaction.touching_into) {
+                if let Some(other) = self.cells.get(py * self.width + px) {
+                    let turned = self.turn(other, into);
+                    self.set(px, py, Some(turned));
+                }
+            }
+            return;
+        }
+    }
+
+    // What a particle becomes in a reaction keeps its heat, or is as hot as its material
+    // when that's hotter
+    fn turn(&mut self, cell: Cell, into: Material) -> Cell {
+        let mut new = self.spawn(into);
+        new.temperature = new.temperature.max(cell.temperature);
+        new
+    }
+
+    // One tick older, tells if the particle is still there
+    fn age(&mut self, x: usize, y: usize) -> bool {
+        let Some(mut cell) = self.cells.get(y * self.width + x) else {
+            return false;
+        };
+
+        cell.tick = self.tick;
+        cell.life = cell.life.saturating_sub(1);
+        if cell.life == 0 {
+            self.set(x, y, None);
+            return false;
+        }
+        self.set(x, y, Some(cell));
+        true
+    }
+}
+
+// The four cells around (x, y), the ones out of the world wrap around to far away
+fn neighbours(x: usize, y: usize) -> [(usize, usize); 4] {
+    [
+        (x.wrapping_sub(1), y),
+        (x + 1, y),
+        (x, y.wrapping_sub(1)),
+        (x, y + 1),
+    ]
+}
@@ -0,0 +1,307 @@
+use std::fs;
+use std::ops::Index;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::material::Properties;
+use crate::{Error, Material};
+
+// The table bundled with the game, see the file for its format
+const BUNDLED: &str = include_str!("../reactions.toml");
+
+/// A particle that turns into another one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reaction {
+    pub material: Material,
+    /// `None` when the particle vanishes.
+    pub into: Option<Material>,
+    pub above: Option<f32>,
+    pub below: Option<f32>,
+    /// A neighbour the particle has to touch, on one of its four sides.
+    pub touching: Option<Material>,
+    /// What that neighbour turns into, it stays as it is when `None`.
+    pub touching_into: Option<Material>,
+    /// On each tick the conditions are met.
+    pub chance: f64,
+}
+
+// A reaction as it is written, with the names of the materials
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Written {
+    material: String,
+    into: Option<String>,
+    above: Option<f32>,
+    below: Option<f32>,
+    touching: Option<String>,
+    touching_into: Option<String>,
+    #[serde(default = "always")]
+    chance: f64,
+}
+
+fn always() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    ambient: f32,
+    air_conductivity: f32,
+    materials: Vec<Properties>,
+    #[serde(default)]
+    reactions: Vec<Written>,
+}
+
+/// The materials, their heat and their reactions, read from a TOML file so that they can
+/// change, or new ones come, without touching the update of the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReactionTable {
+    pub ambient: f32,
+    pub air_conductivity: f32,
+    // Indexed by `Material`, the built-in ones first
+    materials: Vec<Properties>,
+    reactions: Vec<Vec<Reaction>>,
+}
+
+impl ReactionTable {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let file: File = toml::from_str(text)?;
+
+        // The built-in materials keep their place whatever the order of the file
+        let mut materials: Vec<Option<Properties>> = vec![None; Material::BUILT_IN.len()];
+        for properties in file.materials {
+            let name = properties.name.to_lowercase();
+            let taken = |other: &Option<Properties>| {
+                other
+                    .as_ref()
+                    .is_some_and(|other| other.name.to_lowercase() == name)
+            };
+            if materials.iter().any(taken) {
+                return Err(Error::Format(format!("{name} is in the table twice")));
+            }
+            match Material::BUILT_IN
+                .iter()
+                .position(|built_in| *built_in == name)
+            {
+                Some(index) => materials[index] = Some(properties),
+                None => materials.push(Some(properties)),
+            }
+        }
+        if let Some(missing) = materials.iter().position(Option::is_none) {
+            return Err(Error::Format(format!(
+                "{} isn't in the table, the game needs it",
+                Material::BUILT_IN[missing]
+            )));
+        }
+        let materials: Vec<Properties> = materials.into_iter().flatten().collect();
+        // Saves write the material in a byte, with 0 for the empty cells
+        if materials.len() > u8::MAX as usize {
+            return Err(Error::Format(format!(
+                "the table has {} materials, it can't have more than {}",
+                materials.len(),
+                u8::MAX
+            )));
+        }
+
+        for properties in &materials {
+            if properties.colors.is_empty() {
+                return Err(Error::Format(format!("{} has no colour", properties.name)));
+            }
+            if let Some(color) = properties.colors.iter().find(|color| **color > 0xFFFFFF) {
+                return Err(Error::Format(format!(
+                    "{color:#x} isn't a colour of {}, they are written 0xRRGGBB",
+                    properties.name
+                )));
+            }
+        }
+
+        // More than that and the heat would go back and forth further each tick, up to inf
+        let conductivities = materials
+            .iter()
+            .map(|properties| (properties.name.as_str(), properties.conductivity))
+            .chain([("air", file.air_conductivity)]);
+        for (name, conductivity) in conductivities {
+            if !(0.0..=1.0).contains(&conductivity) {
+                return Err(Error::Format(format!(
+                    "the conductivity of {name} is {conductivity}, it has to be between 0 and 1"
+                )));
+            }
+        }
+
+        let mut table = Self {
+            ambient: file.ambient,
+            air_conductivity: file.air_conductivity,
+            reactions: vec![Vec::new(); materials.len()],
+            materials,
+        };
+        for written in file.reactions {
+            if !(0.0..=1.0).contains(&written.chance) {
+                return Err(Error::Format(format!(
+                    "the chance of a {} reaction is {}, it has to be between 0 and 1",
+                    written.material, written.chance
+                )));
+            }
+            let find = |name: Option<String>| name.map(|name| table.material(&name)).transpose();
+            let reaction = Reaction {
+                material: table.material(&written.material)?,
+                into: find(written.into)?,
+                above: written.above,
+                below: written.below,
+                touching: find(written.touching)?,
+                touching_into: find(written.touching_into)?,
+                chance: written.chance,
+            };
+            table.reactions[reaction.material.index()].push(reaction);
+        }
+
+        Ok(table)
+    }
+
+    pub fn load_from_path(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The material with that name, whatever its case.
+    pub fn material(&self, name: &str) -> Result<Material, Error> {
+        self.materials
+            .iter()
+            .position(|properties| properties.name.eq_ignore_ascii_case(name))
+            .map(|index| Material(index as u8))
+            .ok_or_else(|| Error::Format(format!("`{name}` is not a material")))
+    }
+
+    /// Every material of the table, the built-in ones first.
+    pub fn materials(&self) -> impl Iterator<Item = Material> {
+        (0..self.materials.len()).map(|index| Material(index as u8))
+    }
+
+    /// What each material is, in the order of `materials`.
+    pub fn properties(&self) -> &[Properties] {
+        &self.materials
+    }
+
+    /// The material after this one in the table, back to the first one after the last.
+    pub fn next(&self, material: Material) -> Material {
+        Material(((material.index() + 1) % self.materials.len()) as u8)
+    }
+
+    /// How hot a new particle of the material is.
+    pub fn temperature(&self, material: Material) -> f32 {
+        self[material].temperature.unwrap_or(self.ambient)
+    }
+
+    pub fn conductivity(&self, material: Material) -> f32 {
+        self[material].conductivity
+    }
+
+    /// The reactions of a material, in the order of the file.
+    pub fn of(&self, material: Material) -> &[Reaction] {
+        &self.reactions[material.index()]
+    }
+}
+
+impl Index<Material> for ReactionTable {
+    type Output = Properties;
+
+    fn index(&self, material: Material) -> &Properties {
+        &self.materials[material.index()]
+    }
+}
+
+impl Default for ReactionTable {
+    /// The table bundled with the game.
+    fn default() -> Self {
+        Self::parse(BUNDLED).expect("the bundled reactions are valid")
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Movement;
+    use insta::assert_snapshot;
+
+    const MUD: &str =
+        "[[materials]]\nname = \"mud\"\nmovement = \"powder\"\ndensity = 14\ncolors = [0x604830]";
+
+    #[test]
+    fn test_bundled_table() {
+        let table = ReactionTable::default();
+        assert_eq!(table.materials().count(), Material::BUILT_IN.len());
+        assert_eq!(table.temperature(Material::Lava), 1500.0);
+        assert_eq!(table.temperature(Material::Wall), 20.0);
+        assert_eq!(table.conductivity(Material::Wall), 0.0);
+        assert_eq!(table[Material::Oil].movement, Movement::Liquid);
+        assert_eq!(table[Material::Sand].color(), 0xE8C870);
+        assert_eq!(table.material("Water").unwrap(), Material::Water);
+        assert_eq!(
+            table.of(Material::Water)[0],
+            Reaction {
+                material: Material::Water,
+                into: Some(Material::Steam),
+                above: Some(100.0),
+                below: None,
+                touching: None,
+                touching_into: None,
+                chance: 1.0,
+            }
+        );
+        assert_eq!(table.of(Material::Lava).len(), 2);
+    }
+
+    #[test]
+    fn test_new_material() {
+        let text = format!(
+            "{BUNDLED}\n{MUD}\n[[reactions]]\nmaterial = \"mud\"\nabove = 500.0\ninto = \"stone\""
+        );
+        let table = ReactionTable::parse(&text).unwrap();
+        let mud = table.material("mud").unwrap();
+        assert_eq!(mud.index(), Material::BUILT_IN.len());
+        assert_eq!(table[mud].density, 14);
+        assert_eq!(table.of(mud)[0].into, Some(Material::Stone));
+        assert_eq!(table.next(Material::Glass), mud);
+        assert_eq!(table.next(mud), Material::Sand);
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        let error = ReactionTable::parse("ambient = 20.0").unwrap_err();
+        assert_snapshot!(error, @r###"
+        invalid file: TOML parse error at line 1, column 1
+          |
+        1 | ambient = 20.0
+          | ^^^^^^^^^^^^^^
+        missing field `air_conductivity`
+        "###);
+
+        let unknown = format!("{BUNDLED}\n[[reactions]]\nmaterial = \"mud\"");
+        assert_snapshot!(ReactionTable::parse(&unknown).unwrap_err(), @"invalid file: `mud` is not a material");
+
+        let missing = BUNDLED.replace("name = \"glass\"", "name = \"mud\"");
+        assert_snapshot!(ReactionTable::parse(&missing).unwrap_err(), @"invalid file: glass isn't in the table, the game needs it");
+        let twice = format!("{BUNDLED}\n{MUD}\n{MUD}");
+        assert_snapshot!(ReactionTable::parse(&twice).unwrap_err(), @"invalid file: mud is in the table twice");
+        let colorless = format!("{BUNDLED}\n{}", MUD.replace("0x604830", ""));
+        assert_snapshot!(ReactionTable::parse(&colorless).unwrap_err(), @"invalid file: mud has no colour");
+        let movement = format!("{BUNDLED}\n{}", MUD.replace("powder", "plasma"));
+        assert_snapshot!(ReactionTable::parse(&movement).unwrap_err(), @r###"
+        invalid file: TOML parse error at line 166, column 12
+            |
+        166 | movement = "plasma"
+            |            ^^^^^^^^
+        unknown variant `plasma`, expected one of `solid`, `powder`, `liquid`, `gas`, `still`
+        "###);
+
+        let chance = format!("{BUNDLED}\n[[reactions]]\nmaterial = \"sand\"\nchance = 2.0");
+        assert_snapshot!(ReactionTable::parse(&chance).unwrap_err(), @"invalid file: the chance of a sand reaction is 2, it has to be between 0 and 1");
+
+        let conductivity = format!("{BUNDLED}\n{MUD}\nconductivity = 5.0");
+        assert_snapshot!(ReactionTable::parse(&conductivity).unwrap_err(), @"invalid file: the conductivity of mud is 5, it has to be between 0 and 1");
+        let air = BUNDLED.replace("air_conductivity = 0.01", "air_conductivity = nan");
+        assert_snapshot!(ReactionTable::parse(&air).unwrap_err(), @"invalid file: the conductivity of air is NaN, it has to be between 0 and 1");
+    }
+}
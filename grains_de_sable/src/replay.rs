@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use pixel_sims::colour::channels;
+use window_rs::WindowBuffer;
+
+use crate::input::Input;
+use crate::{Error, World};
+
+// REPLAY FORMAT
+// One line per frame: the number of ticks run since the frame before, then the input of the
+// frame (see `Input`). The last line only counts the ticks run after the last frame.
+// A recording is played from the world given by the same options: `--seed`, `--width`,
+// `--height`, `--file-path` and what is in that file, `--level`, `--emitter`, `--reactions`
+// and `--rainbow`. Loading a world stops the recording, it couldn't be replayed after that.
+
+/// One recorded frame, the ticks are run before the input is handled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub ticks: u32,
+    pub input: Input,
+}
+
+/// Writes the input of every frame to a file while the game is played.
+pub struct Recorder {
+    file: BufWriter<File>,
+    ticks: u32,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            ticks: 0,
+        })
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn record(&mut self, input: &Input) -> Result<(), Error> {
+        writeln!(self.file, "{} {input}", self.ticks)?;
+        self.ticks = 0;
+        Ok(())
+    }
+
+    /// Records the ticks run since the last frame.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.record(&Input::default())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read<R: BufRead>(recording: R) -> Result<Vec<Frame>, Error> {
+    let mut frames = Vec::new();
+    for line in recording.lines() {
+        let line = line?;
+        let (ticks, input) = line.split_once(' ').unwrap_or((&line, ""));
+        let ticks = ticks
+            .parse()
+            .map_err(|_| Error::Format(format!("`{line}` doesn't start with a number of ticks")))?;
+        frames.push(Frame {
+            ticks,
+            input: input.parse()?,
+        });
+    }
+    Ok(frames)
+}
+// REPLAY FORMAT END
+
+pub fn load_from_path(path: &Path) -> Result<Vec<Frame>, Error> {
+    read(BufReader::new(File::open(path)?))
+}
+
+/// Plays the frames again on the world, without a window. `snapshot` gets the window after
+/// every `every` ticks when there is a period, and at the end.
+pub fn replay(
+    world: &mut World,
+    frames: &[Frame],
+    every: Option<u64>,
+    mut snapshot: impl FnMut(u64, &WindowBuffer) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut buffer = WindowBuffer::new(world.width(), world.height());
+    let mut tick = 0;
+
+    for frame in frames {
+        for _ in 0..frame.ticks {
+            world.update();
+            tick += 1;
+            if every.is_some_and(|every| every > 0 && tick % every == 0) {
+                world.display(&mut buffer);
+                snapshot(tick, &buffer)?;
+            }
+        }
+        world.handle_input(&frame.input);
+    }
+
+    if every.is_some_and(|every| every > 0 && tick % every == 0) && tick > 0 {
+        // The last tick already has its snapshot
+        return Ok(());
+    }
+    world.display(&mut buffer);
+    snapshot(tick, &buffer)
+}
+
+/// The window as a PNG, it can be loaded back as a level.
+pub fn write_snapshot<W: Write>(buffer: &WindowBuffer, image: W) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(image, buffer.width() as u32, buffer.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+
+    let mut data = Vec::with_capacity(buffer.width() * buffer.height() * 3);
+    for colour in buffer.buffer() {
+        let (red, green, blue) = channels(colour);
+        data.extend([red, green, blue]);
+    }
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+pub fn snapshot_path(snapshot_dir: &Path, tick: u64) -> PathBuf {
+    snapshot_dir.join(format!("snapshot-{tick:06}.png"))
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    // A bug report: a click at the top of a small world, then a wait for the grain to land
+    const RECORDING: &str = "\
+0 2 1 L 0 LeftBracket LeftBracket LeftBracket
+1 - - 0
+6 - - 0
+";
+
+    #[test]
+    fn test_read_recording() {
+        let frames = read(RECORDING.as_bytes()).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].input.mouse, Some((2, 1)));
+        assert_eq!(frames[2].ticks, 6);
+
+        let error = read("- - 0".as_bytes()).unwrap_err();
+        assert_snapshot!(error, @"invalid file: `- - 0` doesn't start with a number of ticks");
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let frames = read(RECORDING.as_bytes()).unwrap();
+        let mut snapshots = Vec::new();
+        let mut world = World::new(5, 4, 0);
+        replay(&mut world, &frames, Some(3), |tick, buffer| {
+            snapshots.push(format!("{tick}\n{buffer}"));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_snapshot!(snapshots.join("\n"), @r###"
+        3
+        .....
+        .....
+        .....
+        ..#..
+
+        6
+        .....
+        .....
+        .....
+        ..#..
+
+        7
+        .....
+        .....
+        .....
+        ..#..
+        "###);
+
+        let mut again = World::new(5, 4, 0);
+        replay(&mut again, &frames, None, |_, _| Ok(())).unwrap();
+        assert_eq!(again.grains(), world.grains());
+    }
+
+    #[test]
+    fn test_record_then_read() {
+        let path = std::env::temp_dir().join("grains_de_sable_test_record");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let input = Input {
+            mouse: Some((2, 1)),
+            left: true,
+            ..Input::default()
+        };
+        recorder.record(&input).unwrap();
+        recorder.tick();
+        recorder.tick();
+        recorder.finish().unwrap();
+
+        let frames = load_from_path(&path).unwrap();
+        assert_eq!(
+            frames,
+            vec![
+                Frame { ticks: 0, input },
+                Frame {
+                    ticks: 2,
+                    input: Input::default()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use pixel_sims::colour::{channels, rgb};
+
+use crate::{Cell, Error, Material, ReactionTable, Sand, World, TERMINAL_VELOCITY};
+
+// SAVE FORMAT
+// `SAND`, the version (u32), width (u64), height (u64), seed (u64), then every cell line by
+// line: its material (u8), 0 for an empty cell or the index of the material in the reaction
+// table plus one. A grain goes on with its colour (u32), life (u8), velocity, offset and
+// temperature (5 f32), all big endian. A world with materials added by a table is loaded
+// with the same table.
+// Sizes are u64 so that saves are the same on 32 bits targets.
+
+const MAGIC: [u8; 4] = *b"SAND";
+const VERSION: u32 = 1;
+
+pub fn write<W: Write>(world: &World, mut save_file: W) -> Result<(), Error> {
+    save_file.write_all(&MAGIC)?;
+    save_file.write_all(&VERSION.to_be_bytes())?;
+    save_file.write_all(&(world.width as u64).to_be_bytes())?;
+    save_file.write_all(&(world.height as u64).to_be_bytes())?;
+    save_file.write_all(&world.seed.to_be_bytes())?;
+
+    for cell in &world.cells {
+        let Some(cell) = cell else {
+            save_file.write_all(&[0])?;
+            continue;
+        };
+        save_file.write_all(&[cell.material.0 + 1])?;
+        save_file.write_all(&cell.color.to_be_bytes())?;
+        save_file.write_all(&[cell.life])?;
+        for number in [
+            cell.velocity.0,
+            cell.velocity.1,
+            cell.offset.0,
+            cell.offset.1,
+            cell.temperature,
+        ] {
+            save_file.write_all(&number.to_be_bytes())?;
+        }
+    }
+
+    save_file.flush()?;
+
+    Ok(())
+}
+
+fn read_chunk<R: Read, const N: usize>(save_file: &mut R) -> Result<[u8; N], Error> {
+    let mut saved_chunk = [0; N];
+    save_file
+        .read_exact(&mut saved_chunk)
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => {
+                Error::Format(String::from("the file ends before the world is complete"))
+            }
+            _ => Error::Io(error),
+        })?;
+    Ok(saved_chunk)
+}
+
+// The size is checked before the world is allocated, a corrupt header could ask for anything
+fn check_size(expected: (usize, usize), found: (usize, usize)) -> Result<(), Error> {
+    if found != expected {
+        return Err(Error::Dimension { expected, found });
+    }
+    Ok(())
+}
+
+/// Reads a world saved with a window of `expected` width and height, and the materials of
+/// `reactions`.
+pub fn read<R: Read>(
+    mut save_file: R,
+    expected: (usize, usize),
+    reactions: &ReactionTable,
+) -> Result<World, Error> {
+    if read_chunk(&mut save_file)? != MAGIC {
+        return Err(Error::Format(String::from("it isn't a save file")));
+    }
+    let version = u32::from_be_bytes(read_chunk(&mut save_file)?);
+    if version != VERSION {
+        return Err(Error::Format(format!(
+            "version {version} of the save format isn't known"
+        )));
+    }
+    let width = u64::from_be_bytes(read_chunk(&mut save_file)?) as usize;
+    let height = u64::from_be_bytes(read_chunk(&mut save_file)?) as usize;
+    let seed = u64::from_be_bytes(read_chunk(&mut save_file)?);
+    check_size(expected, (width, height))?;
+
+    let mut world = World::new(width, height, seed);
+    world.reactions = reactions.clone();
+    let materials = reactions.materials().count();
+
+    for index in 0..width * height {
+        let [material] = read_chunk(&mut save_file)?;
+        if material == 0 {
+            continue;
+        }
+        if material as usize > materials {
+            return Err(Error::Format(format!(
+                "material {} isn't in the reaction table",
+                material - 1
+            )));
+        }
+        let material = Material(material - 1);
+
+        let color = u32::from_be_bytes(read_chunk(&mut save_file)?);
+        let mut cell = Cell::new(material, color, world.tick, reactions);
+        [cell.life] = read_chunk(&mut save_file)?;
+        let mut numbers = [0.0; 5];
+        for number in &mut numbers {
+            *number = f32::from_be_bytes(read_chunk(&mut save_file)?);
+        }
+        if numbers.iter().any(|number| !number.is_finite()) {
+            return Err(Error::Format(String::from(
+                "a grain has a speed or a temperature that isn't a number",
+            )));
+        }
+        // The threads updating the chunks count on grains never going faster than that
+        let speed = |number: f32| number.clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+        cell.velocity = (speed(numbers[0]), speed(numbers[1]));
+        cell.offset = (numbers[2].clamp(-1.0, 1.0), numbers[3].clamp(-1.0, 1.0));
+        cell.temperature = numbers[4];
+        world.cells[index] = Some(cell);
+    }
+
+    Ok(world)
+}
+// SAVE FORMAT END
+
+// PNG
+// One pixel per cell, black when it's empty and the colour of the material of the grain
+// otherwise. When reading, every pixel becomes the material with the closest colour, so a
+// level can be drawn in any image editor. Transparent pixels are empty.
+
+pub fn write_png<W: Write>(world: &World, image: W) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(image, world.width as u32, world.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+
+    let mut data = Vec::with_capacity(world.width * world.height * 3);
+    for cell in &world.cells {
+        let color = cell.map_or(0, |cell| world.reactions[cell.material].color());
+        let (red, green, blue) = channels(color);
+        data.extend([red, green, blue]);
+    }
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// The grains of a level drawn as an image of `expected` width and height, with the
+/// materials of `reactions`. The random choices are seeded with `seed`.
+pub fn read_png<R: Read>(
+    image: R,
+    expected: (usize, usize),
+    seed: u64,
+    reactions: &ReactionTable,
+) -> Result<World, Error> {
+    let mut decoder = png::Decoder::new(image);
+    // Palettes become RGB and 16 bits channels 8 bits
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let header = reader.info();
+    check_size(expected, (header.width as usize, header.height as usize))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let pixel_size = info.color_type.samples();
+    let mut world = World::new(width, height, seed);
+    world.reactions = reactions.clone();
+
+    for (index, pixel) in data[..info.buffer_size()]
+        .chunks_exact(pixel_size)
+        .enumerate()
+    {
+        let (color, opaque) = match info.color_type {
+            png::ColorType::Grayscale => (rgb(pixel[0], pixel[0], pixel[0]), true),
+            png::ColorType::GrayscaleAlpha => (rgb(pixel[0], pixel[0], pixel[0]), pixel[1] >= 128),
+            png::ColorType::Rgba => (rgb(pixel[0], pixel[1], pixel[2]), pixel[3] >= 128),
+            _ => (rgb(pixel[0], pixel[1], pixel[2]), true),
+        };
+        if let Some(material) = closest_material(color, reactions).filter(|_| opaque) {
+            world.add(Sand::new(index % width, index / width, material));
+        }
+    }
+
+    Ok(world)
+}
+
+// None when black is closer than any material
+fn closest_material(color: u32, reactions: &ReactionTable) -> Option<Material> {
+    let distance = |other: u32| {
+        let (red, green, blue) = channels(color);
+        let (other_red, other_green, other_blue) = channels(other);
+        [(red, other_red), (green, other_green), (blue, other_blue)]
+            .into_iter()
+            .map(|(channel, other)| (channel as i32 - other as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    let mut closest = (None, distance(0));
+    for material in reactions.materials() {
+        let material_distance = distance(reactions[material].color());
+        if material_distance < closest.1 {
+            closest = (Some(material), material_distance);
+        }
+    }
+    closest.0
+}
+// PNG END
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+/// Saves as a PNG when the path ends with `.png`, in the save format otherwise.
+pub fn save_to_path(world: &World, path: &Path) -> Result<(), Error> {
+    let file = BufWriter::new(File::create(path)?);
+    if is_png(path) {
+        write_png(world, file)
+    } else {
+        write(world, file)
+    }
+}
+
+/// Loads a level of `expected` width and height from a PNG, with the random choices seeded
+/// with `seed`, or a save file with the seed it was saved with. Both with the materials of
+/// `reactions`.
+pub fn load_from_path(
+    path: &Path,
+    expected: (usize, usize),
+    seed: u64,
+    reactions: &ReactionTable,
+) -> Result<World, Error> {
+    let file = BufReader::new(File::open(path)?);
+    if is_png(path) {
+        read_png(file, expected, seed, reactions)
+    } else {
+        read(file, expected, reactions)
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn table() -> ReactionTable {
+        ReactionTable::default()
+    }
+
+    fn level() -> World {
+        let mut world = World::new(4, 3, 7);
+        world.add(Sand::new(0, 2, Material::Stone));
+        world.add(Sand::new(1, 2, Material::Water));
+        world.add(Sand::new(2, 1, Material::Wood));
+        world.add(Sand {
+            x: 3,
+            y: 0,
+            color: Some(0x00FF00FF),
+            material: Material::Sand,
+        });
+        world
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let mut world = level();
+        world.update();
+
+        let mut bytes = Vec::new();
+        write(&world, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 8 + 3 * 8 + 12 + 4 * (4 + 1 + 5 * 4));
+
+        let loaded = read(&bytes[..], (4, 3), &table()).unwrap();
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(loaded.grains(), world.grains());
+        for (cell, loaded) in world.cells.iter().zip(&loaded.cells) {
+            let state =
+                |cell: &Option<Cell>| cell.map(|cell| (cell.life, cell.velocity, cell.offset));
+            assert_eq!(state(cell), state(loaded));
+        }
+    }
+
+    #[test]
+    fn test_grains_too_fast_are_slowed_down() {
+        let mut bytes = Vec::new();
+        write(&level(), &mut bytes).unwrap();
+        // The velocity of the first grain, after its material, colour and life
+        let velocity = 8 + 3 * 8 + 3 + 1 + 4 + 1;
+        bytes[velocity..velocity + 4].copy_from_slice(&1e9f32.to_be_bytes());
+
+        let loaded = read(&bytes[..], (4, 3), &table()).unwrap();
+        assert_eq!(loaded.cells[3].unwrap().velocity.0, TERMINAL_VELOCITY);
+
+        bytes[velocity..velocity + 4].copy_from_slice(&f32::NAN.to_be_bytes());
+        let error = read(&bytes[..], (4, 3), &table()).err().unwrap();
+        assert_snapshot!(error, @"invalid file: a grain has a speed or a temperature that isn't a number");
+    }
+
+    #[test]
+    fn test_read_something_else() {
+        let error = read(&b"GIF89a"[..], (4, 3), &table()).err().unwrap();
+        assert_snapshot!(error, @"invalid file: it isn't a save file");
+
+        let mut bytes = Vec::new();
+        write(&level(), &mut bytes).unwrap();
+        bytes[4..8].copy_from_slice(&2u32.to_be_bytes());
+        let error = read(&bytes[..], (4, 3), &table()).err().unwrap();
+        assert_snapshot!(error, @"invalid file: version 2 of the save format isn't known");
+    }
+
+    #[test]
+    fn test_materials_come_from_the_table() {
+        let mut world = level();
+        let table = ReactionTable::parse(&format!(
+            "{}\n[[materials]]\nname = \"mud\"\nmovement = \"powder\"\ndensity = 14\ncolors = [0x604830]",
+            include_str!("../reactions.toml")
+        ))
+        .unwrap();
+        let mud = table.material("mud").unwrap();
+        world.set_reactions(table.clone());
+        world.add(Sand::new(0, 0, mud));
+
+        let mut bytes = Vec::new();
+        write(&world, &mut bytes).unwrap();
+        let loaded = read(&bytes[..], (4, 3), &table).unwrap();
+        assert_eq!(loaded.get(0, 0), Some(mud));
+        let error = read(&bytes[..], (4, 3), &ReactionTable::default())
+            .err()
+            .unwrap();
+        assert_snapshot!(error, @"invalid file: material 13 isn't in the reaction table");
+    }
+
+    #[test]
+    fn test_truncated_save() {
+        let mut bytes = Vec::new();
+        write(&level(), &mut bytes).unwrap();
+
+        let error = read(&bytes[..bytes.len() - 1], (4, 3), &table())
+            .err()
+            .unwrap();
+        assert_snapshot!(error, @"invalid file: the file ends before the world is complete");
+        let error = read(&bytes[..8], (4, 3), &table()).err().unwrap();
+        assert_snapshot!(error, @"invalid file: the file ends before the world is complete");
+    }
+
+    #[test]
+    fn test_huge_save_is_refused_before_it_is_allocated() {
+        let mut bytes = [MAGIC.as_slice(), &VERSION.to_be_bytes()].concat();
+        for number in [1u64 << 31, 1 << 31, 0] {
+            bytes.extend_from_slice(&number.to_be_bytes());
+        }
+        let error = read(&bytes[..], (4, 3), &table()).err().unwrap();
+        assert_snapshot!(error, @"the world is 2147483648x2147483648 but the window is 4x3");
+
+        let mut image = Vec::new();
+        write_png(&level(), &mut image).unwrap();
+        let error = read_png(&image[..], (3, 4), 0, &table()).err().unwrap();
+        assert_snapshot!(error, @"the world is 4x3 but the window is 3x4");
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = Vec::new();
+        write_png(&level(), &mut image).unwrap();
+
+        // Sand is saved with the colour of the material, not its own
+        let loaded = read_png(&image[..], (4, 3), 0, &table()).unwrap();
+        let grains: Vec<_> = loaded
+            .grains()
+            .into_iter()
+            .map(|sand| (sand.x, sand.y, sand.material))
+            .collect();
+        assert_eq!(
+            grains,
+            vec![
+                (3, 0, Material::Sand),
+                (2, 1, Material::Wood),
+                (0, 2, Material::Stone),
+                (1, 2, Material::Water),
+            ]
+        );
+        let sand = table()[Material::Sand].color();
+        assert_eq!(loaded.grains()[0].color, Some(sand));
+    }
+
+    #[test]
+    fn test_png_colours_map_to_the_closest_material() {
+        assert_eq!(closest_material(rgb(0x10, 0x10, 0x10), &table()), None);
+        assert_eq!(
+            closest_material(rgb(0xF0, 0xE0, 0x20), &table()),
+            Some(Material::Sand)
+        );
+        assert_eq!(
+            closest_material(rgb(0, 0, 0xFF), &table()),
+            Some(Material::Water)
+        );
+        assert_eq!(
+            closest_material(rgb(0x90, 0x90, 0x90), &table()),
+            Some(Material::Stone)
+        );
+    }
+}
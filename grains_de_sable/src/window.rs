@@ -0,0 +1,21 @@
+use graphic::Graphic;
+
+/// What the game needs from its window on top of `Graphic`: the mouse wheel that sizes the
+/// brush, and a title that follows the particle counts.
+pub trait GameWindow: Graphic {
+    /// How far the wheel turned on this frame, up is positive.
+    fn scroll(&self) -> f32;
+
+    fn set_title(&mut self, title: &str);
+}
+
+// `graphic` can't read the wheel nor rename its windows yet: with the minifb one the brush
+// is only sized with the keys, and the title stays the one it was opened with
+#[cfg(feature = "minifb")]
+impl GameWindow for graphic::minifb::Minifb {
+    fn scroll(&self) -> f32 {
+        0.0
+    }
+
+    fn set_title(&mut self, _title: &str) {}
+}
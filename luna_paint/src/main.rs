@@ -1,12 +1,73 @@
 use minifb::{Key, MouseButton, MouseMode, Scale, Window, WindowOptions};
-
+use pixel_sims::colour::rgb;
+use pixel_sims::main_loop;
+use pixel_sims::Simulation;
+use window_rs::WindowBuffer;
 
 const WIDTH: usize = 620;
 const HEIGHT: usize = 360;
 
-fn main() {
-    let mut buffer = vec![0u32; WIDTH * HEIGHT];
+struct Paint {
+    canvas: WindowBuffer,
+}
+
+impl Paint {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            canvas: WindowBuffer::new(width, height),
+        }
+    }
+
+    // The window is scaled by 2, so the canvas is half its size
+    fn resize(&mut self, width: usize, height: usize) {
+        if width == self.canvas.width() && height == self.canvas.height() {
+            return;
+        }
+
+        let mut canvas = WindowBuffer::new(width, height);
+        // copy valid bits of old canvas to new canvas
+        for y in 0..self.canvas.height().min(height) {
+            for x in 0..self.canvas.width().min(width) {
+                canvas[(x, y)] = self.canvas[(x, y)];
+            }
+        }
+        self.canvas = canvas;
+    }
+
+    fn draw(&mut self, x: usize, y: usize) {
+        if x < self.canvas.width() && y < self.canvas.height() {
+            self.canvas[(x, y)] = rgb(u8::MAX, u8::MAX, u8::MAX); // white
+        }
+    }
+}
+
+impl Simulation<Window> for Paint {
+    fn update(&mut self) {}
+
+    fn render(&self, buffer: &mut WindowBuffer) {
+        if buffer.width() != self.canvas.width() || buffer.height() != self.canvas.height() {
+            *buffer = WindowBuffer::new(self.canvas.width(), self.canvas.height());
+        }
+        for y in 0..self.canvas.height() {
+            for x in 0..self.canvas.width() {
+                buffer[(x, y)] = self.canvas[(x, y)];
+            }
+        }
+    }
 
+    fn handle_input(&mut self, window: &Window) {
+        let (width, height) = window.get_size();
+        self.resize(width / 2, height / 2);
+
+        if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
+            if window.get_mouse_down(MouseButton::Left) {
+                self.draw(x as usize, y as usize);
+            }
+        }
+    }
+}
+
+fn main() {
     let mut window = Window::new(
         "Mouse drawing example - press ESC to exit",
         WIDTH,
@@ -21,36 +82,14 @@ fn main() {
     // Limit to max ~60 fps update rate
     window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
-    let (mut width, mut height) = (WIDTH, HEIGHT);
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let (new_width, new_height) = window.get_size();
-        if new_width != width || new_height != height {
-            let mut new_buffer = vec![0; (new_width) * (new_height)];
-
-            // copy valid bits of old buffer to new buffer
-            for y in 0..(height).min(new_height) {
-                for x in 0..(width).min(new_width) {
-                    new_buffer[y * (new_width) + x] = buffer[y * (width) + x];
-                }
-            }
-
-            buffer = new_buffer;
-            width = new_width;
-            height = new_height;
-        }
-
-        if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
-            let screen_pos = ((y as usize) * (width / 2)) + x as usize;
-
-            if window.get_mouse_down(MouseButton::Left) {
-                buffer[screen_pos] = 0x00ffffff; // white
-            }
-        }
+    let mut paint = Paint::new(WIDTH, HEIGHT);
+    let mut buffer = WindowBuffer::new(WIDTH, HEIGHT);
 
+    main_loop::run(&mut window, &mut paint, &mut buffer, |window, buffer| {
         // We unwrap here as we want this code to exit if it fails
         window
-            .update_with_buffer(&buffer, width / 2, height / 2)
+            .update_with_buffer(&buffer.buffer(), buffer.width(), buffer.height())
             .unwrap();
-    }
-}
\ No newline at end of file
+        window.is_open() && !window.is_key_down(Key::Escape)
+    });
+}
@@ -0,0 +1,189 @@
+//COLOURS MANAGEMENT
+// Pixels are stored as 0xAARRGGBB, the window ignores the alpha channel.
+
+pub const fn rgb(red: u8, green: u8, blue: u8) -> u32 {
+    u32::from_be_bytes([0, red, green, blue])
+}
+
+pub const fn argb(alpha: u8, red: u8, green: u8, blue: u8) -> u32 {
+    u32::from_be_bytes([alpha, red, green, blue])
+}
+
+/// The (red, green, blue) channels of a pixel.
+pub fn channels(colour: u32) -> (u8, u8, u8) {
+    let [_, red, green, blue] = colour.to_be_bytes();
+    (red, green, blue)
+}
+
+pub fn alpha(colour: u32) -> u8 {
+    colour.to_be_bytes()[0]
+}
+
+/// `hue` in degrees, `saturation` and `value` between 0 and 1.
+pub fn hsv(hue: f32, saturation: f32, value: f32) -> u32 {
+    let hue = hue.rem_euclid(360.0);
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - chroma;
+
+    let (red, green, blue) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+    rgb(channel(red), channel(green), channel(blue))
+}
+
+/// The (hue, saturation, value) of a pixel, in the ranges used by `hsv`.
+pub fn to_hsv(colour: u32) -> (f32, f32, f32) {
+    let (red, green, blue) = channels(colour);
+    let (red, green, blue) = (
+        red as f32 / 255.0,
+        green as f32 / 255.0,
+        blue as f32 / 255.0,
+    );
+
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+/// Goes from `from` to `to` as `t` goes from 0 to 1.
+pub fn mix(from: u32, to: u32, t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    let (from, to) = (from.to_be_bytes(), to.to_be_bytes());
+    let channel = |i: usize| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t).round() as u8;
+
+    u32::from_be_bytes([channel(0), channel(1), channel(2), channel(3)])
+}
+
+/// Multiplies the brightness of a pixel, 0 is black and 1 leaves it as is.
+pub fn shade(colour: u32, factor: f32) -> u32 {
+    let [alpha, red, green, blue] = colour.to_be_bytes();
+    let channel = |c: u8| (c as f32 * factor).round().clamp(0.0, 255.0) as u8;
+
+    argb(alpha, channel(red), channel(green), channel(blue))
+}
+//COLOURS MANAGEMENT END
+
+// PALETTES
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub colours: Vec<u32>,
+}
+
+impl Palette {
+    pub fn new(colours: Vec<u32>) -> Self {
+        Self { colours }
+    }
+
+    /// `size` colours spread evenly around the hue circle.
+    pub fn rainbow(size: usize) -> Self {
+        let colours = (0..size)
+            .map(|index| hsv(index as f32 * 360.0 / size as f32, 1.0, 1.0))
+            .collect();
+        Self { colours }
+    }
+
+    /// The colour at `index`, wrapping around at the end of the palette.
+    pub fn get(&self, index: usize) -> u32 {
+        self.colours[index % self.colours.len()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.colours.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colours.is_empty()
+    }
+}
+
+pub fn color_generator() -> impl Iterator<Item = (u8, u8, u8)> {
+    let channel = (0..u8::MAX) // On monte jusqu’à u8::MAX de 1 en 1
+        .chain(std::iter::repeat_n(u8::MAX, u8::MAX as usize * 2)) // On reste a u8::MAX PENDANT u8::MAX itération pour que l’autre channel puisse nous rejoindre
+        .chain((0..=u8::MAX).rev()) // On redescend jusqu’à 0
+        .chain(std::iter::repeat_n(0, u8::MAX as usize * 2)) // On reste a 0 pendant u8::MAX * 2
+        .cycle(); // On répète tout ça a l’infini
+
+    channel
+        .clone()
+        .skip(u8::MAX as usize * 2)
+        .zip(channel.clone())
+        .zip(channel.clone().skip(u8::MAX as usize * 4))
+        .map(|((r, g), b)| (r, g, b))
+}
+// PALETTES END
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_rgb() {
+        assert_eq!(rgb(0, 0, 0), 0x00_00_00_00);
+        assert_eq!(rgb(255, 255, 255), 0x00_ff_ff_ff);
+        assert_eq!(rgb(0x12, 0x34, 0x56), 0x00_12_34_56);
+        assert_eq!(argb(0x78, 0x12, 0x34, 0x56), 0x78_12_34_56);
+    }
+
+    #[test]
+    fn test_hsv() {
+        assert_eq!(hsv(0.0, 1.0, 1.0), rgb(255, 0, 0));
+        assert_eq!(hsv(120.0, 1.0, 1.0), rgb(0, 255, 0));
+        assert_eq!(hsv(240.0, 1.0, 1.0), rgb(0, 0, 255));
+        assert_eq!(hsv(60.0, 1.0, 0.5), rgb(128, 128, 0));
+        assert_eq!(hsv(42.0, 0.0, 1.0), rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn test_mix_and_shade() {
+        assert_eq!(mix(rgb(0, 0, 0), rgb(200, 100, 50), 0.5), rgb(100, 50, 25));
+        assert_eq!(shade(rgb(200, 100, 50), 0.5), rgb(100, 50, 25));
+        assert_eq!(shade(rgb(200, 100, 50), 2.0), rgb(255, 200, 100));
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::rainbow(3);
+        assert_eq!(palette.colours, vec![rgb(255, 0, 0), rgb(0, 255, 0), rgb(0, 0, 255)]);
+        assert_eq!(palette.get(4), rgb(0, 255, 0));
+    }
+
+    proptest! {
+        #[test]
+        fn test_channels(red in 0u8.., green in 0u8.., blue in 0u8..) {
+            prop_assert_eq!(channels(rgb(red, green, blue)), (red, green, blue));
+        }
+
+        #[test]
+        fn test_hsv_round_trip(red in 0u8.., green in 0u8.., blue in 0u8..) {
+            let (hue, saturation, value) = to_hsv(rgb(red, green, blue));
+            prop_assert_eq!(hsv(hue, saturation, value), rgb(red, green, blue));
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use web_time::Duration;
+use window_rs::WindowBuffer;
+
+pub mod colour;
+pub mod main_loop;
+
+/// What every sim gives to the main loop.
+/// `W` is the window the input is read from.
+pub trait Simulation<W> {
+    /// Moves the simulation forward by one tick.
+    fn update(&mut self);
+
+    /// Draws the current state of the simulation in the buffer.
+    fn render(&self, buffer: &mut WindowBuffer);
+
+    /// Reacts to the mouse and the keyboard, called once per frame.
+    fn handle_input(&mut self, window: &W);
+
+    /// Time between two updates, zero means one update per frame.
+    fn tick(&self) -> Duration {
+        Duration::from_micros(16600)
+    }
+}
@@ -0,0 +1,150 @@
+use web_time::{Duration, Instant};
+use window_rs::WindowBuffer;
+
+use crate::Simulation;
+
+// After a long pause (window dragged, debugger...) the sim doesn't try to catch up
+// on every tick it missed, it would freeze the window even longer.
+pub const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Counts how many updates are due, so that a sim runs at the same speed whatever the frame rate.
+#[derive(Debug)]
+pub struct FixedTimestep {
+    pub tick: Duration,
+    accumulator: Duration,
+    last_frame: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(tick: Duration) -> Self {
+        Self {
+            tick,
+            accumulator: Duration::ZERO,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Number of updates to run for the time elapsed since the last call.
+    pub fn ticks(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last_frame;
+        self.last_frame = now;
+
+        self.ticks_for(elapsed)
+    }
+
+    pub fn ticks_for(&mut self, elapsed: Duration) -> u32 {
+        if self.tick.is_zero() {
+            return 1;
+        }
+
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick && ticks < MAX_TICKS_PER_FRAME {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        if ticks == MAX_TICKS_PER_FRAME {
+            self.accumulator = Duration::ZERO;
+        }
+
+        ticks
+    }
+}
+
+/// Runs one frame: input, as many updates as the time elapsed asks for, then render.
+pub fn frame<W, S: Simulation<W>>(
+    window: &W,
+    simulation: &mut S,
+    timestep: &mut FixedTimestep,
+    buffer: &mut WindowBuffer,
+) {
+    simulation.handle_input(window);
+
+    timestep.tick = simulation.tick();
+    for _ in 0..timestep.ticks() {
+        simulation.update();
+    }
+
+    simulation.render(buffer);
+}
+
+/// The main loop shared by the sims. `present` shows the buffer in the window and tells
+/// whether the loop should go on.
+pub fn run<W, S: Simulation<W>>(
+    window: &mut W,
+    simulation: &mut S,
+    buffer: &mut WindowBuffer,
+    mut present: impl FnMut(&mut W, &WindowBuffer) -> bool,
+) {
+    let mut timestep = FixedTimestep::new(simulation.tick());
+
+    loop {
+        frame(window, simulation, &mut timestep, buffer);
+
+        if !present(window, buffer) {
+            break;
+        }
+    }
+}
+
+//TESTS
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fixed_timestep() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+        assert_eq!(timestep.ticks_for(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.ticks_for(Duration::from_millis(7)), 1);
+        assert_eq!(timestep.ticks_for(Duration::from_millis(29)), 3);
+        assert_eq!(timestep.ticks_for(Duration::from_secs(10)), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.ticks_for(Duration::from_millis(9)), 0);
+
+        let mut every_frame = FixedTimestep::new(Duration::ZERO);
+        assert_eq!(every_frame.ticks_for(Duration::from_millis(1)), 1);
+    }
+
+    struct Counter {
+        updates: usize,
+        inputs: usize,
+    }
+
+    impl Simulation<()> for Counter {
+        fn update(&mut self) {
+            self.updates += 1;
+        }
+
+        fn render(&self, buffer: &mut WindowBuffer) {
+            buffer[(0, 0)] = self.updates as u32;
+        }
+
+        fn handle_input(&mut self, _window: &()) {
+            self.inputs += 1;
+        }
+
+        fn tick(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let mut counter = Counter {
+            updates: 0,
+            inputs: 0,
+        };
+        let mut buffer = WindowBuffer::new(1, 1);
+        let mut frames = 0;
+
+        run(&mut (), &mut counter, &mut buffer, |_, _| {
+            frames += 1;
+            frames < 3
+        });
+
+        assert_eq!((counter.inputs, counter.updates), (3, 3));
+        assert_eq!(buffer[(0, 0)], 3);
+    }
+}